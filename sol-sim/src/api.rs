@@ -1,11 +1,18 @@
 use crate::{
-    fork::ForkManager, CreateForkRequest, CreateForkResponse, DeleteForkResponse, ErrorDetails,
-    ErrorResponse, ForkId, GetForkResponse, HealthResponse, JsonRpcRequest,
+    archive::ForkArchive,
+    diff::ForkDiff,
+    fork::{ForkExpired, ForkManager, InvalidRequest},
+    upstream::RpcUrlNotAllowed,
+    CacheStatsResponse, CreateForkRequest, CreateForkResponse, DeleteForkResponse, ErrorDetails,
+    ErrorResponse, ExtendForkRequest, ForkDiffResponse, ForkId, ForkInfo, ForkPlanResponse,
+    GetForkResponse, HealthResponse, JsonRpcRequest, PatchForkResponse, ResetForkQuery,
+    ResetForkResponse,
 };
 use axum::response::IntoResponse;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
     response::Json,
 };
 use std::sync::Arc;
@@ -36,6 +43,77 @@ fn format_duration(duration: std::time::Duration) -> String {
     }
 }
 
+/// Parse a fork id from a request path
+fn parse_fork_id(fork_id: &str) -> Result<ForkId, (StatusCode, Json<ErrorResponse>)> {
+    fork_id.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse {
+                success: false,
+                error: ErrorDetails {
+                    code: "INVALID_FORK_ID".to_string(),
+                    message: "Invalid fork ID format".to_string(),
+                    details: None,
+                },
+            }),
+        )
+    })
+}
+
+/// Response for requests to a fork that doesn't exist or was deleted
+fn fork_not_found(fork_id: &ForkId) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::NOT_FOUND,
+        Json(ErrorResponse {
+            success: false,
+            error: ErrorDetails {
+                code: "FORK_NOT_FOUND".to_string(),
+                message: format!("Fork {} not found or already deleted", fork_id),
+                details: None,
+            },
+        }),
+    )
+}
+
+/// Response for requests to a fork that was evicted after expiring
+fn fork_expired(fork_id: &ForkId) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::GONE,
+        Json(ErrorResponse {
+            success: false,
+            error: ErrorDetails {
+                code: "FORK_EXPIRED".to_string(),
+                message: format!("Fork {} expired", fork_id),
+                details: None,
+            },
+        }),
+    )
+}
+
+/// Response for a fork creation or import whose `rpcUrl` the operator hasn't allowed
+fn rpc_url_not_allowed(e: &anyhow::Error) -> (StatusCode, Json<ErrorResponse>) {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(ErrorResponse {
+            success: false,
+            error: ErrorDetails {
+                code: "RPC_URL_NOT_ALLOWED".to_string(),
+                message: "rpcUrl is not allowed by this server".to_string(),
+                details: Some(e.to_string()),
+            },
+        }),
+    )
+}
+
+/// 400 for requests the client has to fix, 500 for failures while serving them
+fn error_status(e: &anyhow::Error) -> StatusCode {
+    if e.is::<InvalidRequest>() {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+}
+
 /// Health check
 pub async fn health() -> Json<HealthResponse> {
     let uptime = START_TIME
@@ -52,13 +130,26 @@ pub async fn health() -> Json<HealthResponse> {
     })
 }
 
+/// Shared upstream account cache statistics
+pub async fn cache_stats(State(manager): State<AppState>) -> Json<CacheStatsResponse> {
+    let stats = manager.cache_stats();
+    Json(CacheStatsResponse {
+        success: true,
+        entries: stats.entries,
+        bytes: stats.bytes,
+        hits: stats.hits,
+        misses: stats.misses,
+        evictions: stats.evictions,
+    })
+}
+
 /// Create a new fork
 pub async fn create_fork(
     State(manager): State<AppState>,
     Json(req): Json<CreateForkRequest>,
 ) -> Result<(StatusCode, Json<CreateForkResponse>), (StatusCode, Json<ErrorResponse>)> {
-    match manager.create_fork(req.accounts).await {
-        Ok(fork_info) => {
+    match manager.create_fork(req).await {
+        Ok((fork_info, report, transaction)) => {
             let response = CreateForkResponse {
                 success: true,
                 fork_id: fork_info.fork_id.to_string(),
@@ -66,14 +157,18 @@ pub async fn create_fork(
                 created_at: fork_info.created_at,
                 expires_at: fork_info.expires_at,
                 account_count: fork_info.account_count,
-                ttl_minutes: 15,
+                ttl_minutes: fork_info.ttl_minutes,
+                source_slot: fork_info.source_slot,
+                transaction,
+                report: Some(report),
             };
             Ok((StatusCode::CREATED, Json(response)))
         }
+        Err(e) if e.is::<RpcUrlNotAllowed>() => Err(rpc_url_not_allowed(&e)),
         Err(e) => {
             error!("Failed to create fork: {}", e);
             Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
+                error_status(&e),
                 Json(ErrorResponse {
                     success: false,
                     error: ErrorDetails {
@@ -87,51 +182,312 @@ pub async fn create_fork(
     }
 }
 
-/// Get fork status
-pub async fn get_fork(
+/// Resolve the accounts a fork creation request would load, without creating the fork
+pub async fn plan_fork(
+    State(manager): State<AppState>,
+    Json(req): Json<CreateForkRequest>,
+) -> Result<Json<ForkPlanResponse>, (StatusCode, Json<ErrorResponse>)> {
+    match manager.plan_fork(req).await {
+        Ok((accounts, report, source_slot)) => Ok(Json(ForkPlanResponse {
+            success: true,
+            total_bytes: accounts.iter().map(|account| account.size as u64).sum(),
+            accounts,
+            source_slot,
+            missing: report.missing,
+            failed: report.failed,
+        })),
+        Err(e) if e.is::<RpcUrlNotAllowed>() => Err(rpc_url_not_allowed(&e)),
+        Err(e) => {
+            error!("Failed to plan fork: {}", e);
+            Err((
+                error_status(&e),
+                Json(ErrorResponse {
+                    success: false,
+                    error: ErrorDetails {
+                        code: "FORK_PLAN_FAILED".to_string(),
+                        message: "Failed to plan fork".to_string(),
+                        details: Some(e.to_string()),
+                    },
+                }),
+            ))
+        }
+    }
+}
+
+/// Clone a fork into a new independent fork
+pub async fn clone_fork(
     State(manager): State<AppState>,
     Path(fork_id): Path<String>,
-) -> Result<Json<GetForkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let fork_id: ForkId = fork_id.parse().map_err(|_| {
-        (
+) -> Result<(StatusCode, Json<CreateForkResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+
+    match manager.clone_fork(&fork_id).await {
+        Ok(Some(fork_info)) => {
+            let response = CreateForkResponse {
+                success: true,
+                fork_id: fork_info.fork_id.to_string(),
+                rpc_url: fork_info.rpc_url.clone(),
+                created_at: fork_info.created_at,
+                expires_at: fork_info.expires_at,
+                account_count: fork_info.account_count,
+                ttl_minutes: fork_info.ttl_minutes,
+                source_slot: fork_info.source_slot,
+                transaction: None,
+                report: None,
+            };
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
+        Err(e) => {
+            error!("Failed to clone fork {}: {}", fork_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    success: false,
+                    error: ErrorDetails {
+                        code: "FORK_CLONE_FAILED".to_string(),
+                        message: "Failed to clone fork".to_string(),
+                        details: Some(e.to_string()),
+                    },
+                }),
+            ))
+        }
+    }
+}
+
+/// Restore a fork to its creation state, keeping its id and RPC URL
+pub async fn reset_fork(
+    State(manager): State<AppState>,
+    Path(fork_id): Path<String>,
+    Query(query): Query<ResetForkQuery>,
+) -> Result<Json<ResetForkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+
+    match manager.reset_fork(&fork_id, query.refetch).await {
+        Ok(Some((info, slot))) => Ok(Json(ResetForkResponse {
+            success: true,
+            fork_id: info.fork_id.to_string(),
+            rpc_url: info.rpc_url,
+            slot,
+            refetched: query.refetch,
+        })),
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
+        Err(e) => {
+            error!("Failed to reset fork {}: {}", fork_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    success: false,
+                    error: ErrorDetails {
+                        code: "RESET_FAILED".to_string(),
+                        message: "Failed to reset fork".to_string(),
+                        details: Some(e.to_string()),
+                    },
+                }),
+            ))
+        }
+    }
+}
+
+/// Diff a fork against the state it was created with
+pub async fn diff_fork(
+    State(manager): State<AppState>,
+    Path(fork_id): Path<String>,
+) -> Result<Json<ForkDiffResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+
+    match manager.diff_fork(&fork_id).await {
+        Ok(Some(diff)) => Ok(Json(ForkDiffResponse {
+            success: true,
+            fork_id: fork_id.to_string(),
+            diff,
+        })),
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
+        Err(e) => {
+            error!("Failed to diff fork {}: {}", fork_id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    success: false,
+                    error: ErrorDetails {
+                        code: "INTERNAL_ERROR".to_string(),
+                        message: "Failed to diff fork".to_string(),
+                        details: Some(e.to_string()),
+                    },
+                }),
+            ))
+        }
+    }
+}
+
+/// Apply a diff (as returned by `GET /forks/{fork_id}/diff`) to a fork
+pub async fn patch_fork(
+    State(manager): State<AppState>,
+    Path(fork_id): Path<String>,
+    Json(diff): Json<ForkDiff>,
+) -> Result<Json<PatchForkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+
+    match manager.patch_fork(&fork_id, &diff).await {
+        Ok(Some(())) => Ok(Json(PatchForkResponse {
+            success: true,
+            fork_id: fork_id.to_string(),
+            created: diff.created.len(),
+            deleted: diff.deleted.len(),
+            modified: diff.modified.len(),
+        })),
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
+        Err(e) => Err((
             StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
                 success: false,
                 error: ErrorDetails {
-                    code: "INVALID_FORK_ID".to_string(),
-                    message: "Invalid fork ID format".to_string(),
-                    details: None,
+                    code: "PATCH_FAILED".to_string(),
+                    message: "Failed to apply diff".to_string(),
+                    details: Some(e.to_string()),
+                },
+            }),
+        )),
+    }
+}
+
+/// Export a fork's complete state as a portable archive
+pub async fn export_fork(
+    State(manager): State<AppState>,
+    Path(fork_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+
+    let export_error = |e: anyhow::Error| {
+        error!("Failed to export fork {}: {}", fork_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                success: false,
+                error: ErrorDetails {
+                    code: "EXPORT_FAILED".to_string(),
+                    message: "Failed to export fork".to_string(),
+                    details: Some(e.to_string()),
                 },
             }),
         )
-    })?;
+    };
 
-    match manager.touch_fork(&fork_id).await {
-        Ok(Some(info)) => Ok(Json(GetForkResponse {
-            success: true,
-            fork_id: info.fork_id.to_string(),
-            rpc_url: info.rpc_url.clone(),
-            status: if info.is_expired() {
-                "expired".to_string()
-            } else {
-                "active".to_string()
-            },
-            created_at: info.created_at,
-            expires_at: info.expires_at,
-            remaining_minutes: info.remaining_minutes(),
-            account_count: info.account_count,
-        })),
-        Ok(None) => Err((
-            StatusCode::NOT_FOUND,
+    match manager.export_fork(&fork_id).await {
+        Ok(Some(archive)) => {
+            let bytes = archive.to_bytes().map_err(export_error)?;
+            Ok((
+                [
+                    (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"fork-{}.solsim\"", fork_id),
+                    ),
+                ],
+                bytes,
+            ))
+        }
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
+        Err(e) => Err(export_error(e)),
+    }
+}
+
+/// Create a fork from an exported archive
+pub async fn import_fork(
+    State(manager): State<AppState>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<CreateForkResponse>), (StatusCode, Json<ErrorResponse>)> {
+    let archive = ForkArchive::from_bytes(&body).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
             Json(ErrorResponse {
                 success: false,
                 error: ErrorDetails {
-                    code: "FORK_NOT_FOUND".to_string(),
-                    message: "Fork not found or already deleted".to_string(),
-                    details: None,
+                    code: "INVALID_ARCHIVE".to_string(),
+                    message: "Invalid fork archive".to_string(),
+                    details: Some(e.to_string()),
                 },
             }),
-        )),
+        )
+    })?;
+
+    match manager.import_fork(archive).await {
+        Ok(fork_info) => {
+            let response = CreateForkResponse {
+                success: true,
+                fork_id: fork_info.fork_id.to_string(),
+                rpc_url: fork_info.rpc_url.clone(),
+                created_at: fork_info.created_at,
+                expires_at: fork_info.expires_at,
+                account_count: fork_info.account_count,
+                ttl_minutes: fork_info.ttl_minutes,
+                source_slot: fork_info.source_slot,
+                transaction: None,
+                report: None,
+            };
+            Ok((StatusCode::CREATED, Json(response)))
+        }
+        Err(e) if e.is::<RpcUrlNotAllowed>() => Err(rpc_url_not_allowed(&e)),
+        Err(e) => {
+            error!("Failed to import fork: {}", e);
+            Err((
+                error_status(&e),
+                Json(ErrorResponse {
+                    success: false,
+                    error: ErrorDetails {
+                        code: "IMPORT_FAILED".to_string(),
+                        message: "Failed to import fork".to_string(),
+                        details: Some(e.to_string()),
+                    },
+                }),
+            ))
+        }
+    }
+}
+
+fn fork_response(info: &ForkInfo) -> GetForkResponse {
+    GetForkResponse {
+        success: true,
+        fork_id: info.fork_id.to_string(),
+        rpc_url: info.rpc_url.clone(),
+        status: if info.is_expired() {
+            "expired".to_string()
+        } else {
+            "active".to_string()
+        },
+        created_at: info.created_at,
+        expires_at: info.expires_at,
+        remaining_minutes: info.remaining_minutes(),
+        ttl_minutes: info.ttl_minutes,
+        pinned: info.pinned,
+        refresh_on_touch: info.refresh_on_touch,
+        account_count: info.account_count,
+        source_slot: info.source_slot,
+        commitment: info.commitment,
+        cluster: info.cluster.clone(),
+        upstream_url: info.upstream_url.clone(),
+        idl: info.idl,
+        limits: info.limits.clone(),
+        chain_sync: info.chain_sync.clone(),
+    }
+}
+
+/// Get fork status
+pub async fn get_fork(
+    State(manager): State<AppState>,
+    Path(fork_id): Path<String>,
+) -> Result<Json<GetForkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+
+    match manager.touch_fork(&fork_id).await {
+        Ok(Some(info)) => Ok(Json(fork_response(&info))),
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(ErrorResponse {
@@ -146,24 +502,39 @@ pub async fn get_fork(
     }
 }
 
-/// Delete a fork
-pub async fn delete_fork(
+/// Change a fork's TTL or pin and restart its lifetime
+pub async fn extend_fork(
     State(manager): State<AppState>,
     Path(fork_id): Path<String>,
-) -> Result<Json<DeleteForkResponse>, (StatusCode, Json<ErrorResponse>)> {
-    let fork_id: ForkId = fork_id.parse().map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
+    body: Option<Json<ExtendForkRequest>>,
+) -> Result<Json<GetForkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
+    let req = body.map(|Json(req)| req).unwrap_or_default();
+
+    match manager.extend_fork(&fork_id, &req).await {
+        Ok(Some(info)) => Ok(Json(fork_response(&info))),
+        Ok(None) => Err(fork_not_found(&fork_id)),
+        Err(e) if e.is::<ForkExpired>() => Err(fork_expired(&fork_id)),
+        Err(e) => Err((
+            error_status(&e),
             Json(ErrorResponse {
                 success: false,
                 error: ErrorDetails {
-                    code: "INVALID_FORK_ID".to_string(),
-                    message: "Invalid fork ID format".to_string(),
-                    details: None,
+                    code: "EXTEND_FAILED".to_string(),
+                    message: "Failed to extend fork".to_string(),
+                    details: Some(e.to_string()),
                 },
             }),
-        )
-    })?;
+        )),
+    }
+}
+
+/// Delete a fork
+pub async fn delete_fork(
+    State(manager): State<AppState>,
+    Path(fork_id): Path<String>,
+) -> Result<Json<DeleteForkResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let fork_id = parse_fork_id(&fork_id)?;
 
     match manager.delete_fork(&fork_id).await {
         Ok(_) => Ok(Json(DeleteForkResponse {
@@ -194,17 +565,42 @@ pub async fn handle_rpc(
     let fork_id: ForkId = match fork_id.parse() {
         Ok(id) => id,
         Err(_) => {
-            return Json(crate::JsonRpcResponse {
-                jsonrpc: "2.0".to_string(),
-                id: req.id,
-                result: None,
-                error: Some(crate::JsonRpcError {
-                    code: -32602,
-                    message: "Invalid fork ID".to_string(),
+            return (
+                StatusCode::OK,
+                Json(crate::JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: req.id,
+                    result: None,
+                    error: Some(crate::JsonRpcError {
+                        code: -32602,
+                        message: "Invalid fork ID".to_string(),
+                    }),
                 }),
-            });
+            );
         }
     };
 
-    Json(manager.handle_rpc(&fork_id, req).await)
+    // Expired forks answer with 410 like the REST endpoints; other failures of the check are
+    // reported by `handle_rpc` as JSON-RPC errors
+    if let Err(e) = manager.ensure_not_expired(&fork_id).await {
+        if e.is::<ForkExpired>() {
+            return (
+                StatusCode::GONE,
+                Json(crate::JsonRpcResponse {
+                    jsonrpc: "2.0".to_string(),
+                    id: req.id,
+                    result: None,
+                    error: Some(crate::JsonRpcError {
+                        code: -32603,
+                        message: e.to_string(),
+                    }),
+                }),
+            );
+        }
+    }
+
+    (
+        StatusCode::OK,
+        Json(manager.handle_rpc(&fork_id, req).await),
+    )
 }
@@ -0,0 +1,144 @@
+use crate::{BlockProduction, Commitment, FetchLimits, TransactionRecord};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{account::Account, hash::Hash, pubkey::Pubkey};
+use std::collections::VecDeque;
+
+/// Current archive format version
+pub const ARCHIVE_VERSION: u32 = 1;
+
+/// Leading bytes identifying a fork archive
+const ARCHIVE_MAGIC: &[u8; 8] = b"SOLSIMFK";
+
+/// Describes an exported fork; stored as JSON at the start of the archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub version: u32,
+    #[serde(rename = "forkId")]
+    pub fork_id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    #[serde(rename = "accountCount")]
+    pub account_count: usize,
+    /// Latest blockhash
+    pub blockhash: String,
+    /// Blockhashes transactions may be signed against, oldest first and ending with
+    /// `blockhash`. Archives without them accept only `blockhash`.
+    #[serde(rename = "recentBlockhashes", default)]
+    pub recent_blockhashes: Vec<String>,
+    #[serde(rename = "pendingClockMs")]
+    pub pending_clock_ms: i64,
+    #[serde(rename = "blockProduction")]
+    pub block_production: BlockProduction,
+    pub transactions: Vec<TransactionRecord>,
+    /// Upstream slot the source fork was created from
+    #[serde(rename = "sourceSlot", default)]
+    pub source_slot: Option<u64>,
+    /// Commitment the source fork fetched accounts with
+    #[serde(default)]
+    pub commitment: Commitment,
+    /// Named upstream cluster the source fork fetched accounts from
+    #[serde(default)]
+    pub cluster: Option<String>,
+    /// Ad-hoc upstream endpoint the source fork used instead of a cluster
+    #[serde(rename = "upstreamUrl", default)]
+    pub upstream_url: Option<String>,
+    /// Anchor IDL accounts of programs are fetched along with them
+    #[serde(default)]
+    pub idl: bool,
+    /// Bounds the source fork's accounts were fetched with
+    #[serde(default)]
+    pub limits: FetchLimits,
+    /// Accounts requested when the fork was created, refetched by `reset?refetch=true`
+    #[serde(rename = "sourceAccounts", default)]
+    pub source_accounts: Vec<String>,
+    /// Missing accounts are fetched from upstream on first use
+    #[serde(default)]
+    pub lazy: bool,
+    /// Accounts confirmed not to exist upstream
+    #[serde(rename = "missingAccounts", default)]
+    pub missing_accounts: Vec<String>,
+}
+
+impl ArchiveManifest {
+    /// Set `blockhash` and `recent_blockhashes` from a fork's blockhash window, oldest first
+    pub fn with_recent_blockhashes(mut self, blockhashes: &VecDeque<Hash>) -> Self {
+        self.blockhash = blockhashes.back().map(Hash::to_string).unwrap_or_default();
+        self.recent_blockhashes = blockhashes.iter().map(Hash::to_string).collect();
+        self
+    }
+
+    /// The archived blockhash window, oldest first
+    pub fn recent_blockhashes(&self) -> Result<VecDeque<Hash>> {
+        let blockhashes = match self.recent_blockhashes.is_empty() {
+            true => std::slice::from_ref(&self.blockhash),
+            false => self.recent_blockhashes.as_slice(),
+        };
+        blockhashes
+            .iter()
+            .map(|blockhash| {
+                blockhash.parse().map_err(|e| {
+                    anyhow::anyhow!("Invalid archived blockhash '{}': {}", blockhash, e)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Portable copy of a fork's complete state.
+///
+/// Binary layout:
+/// - Bytes 0-7: magic `SOLSIMFK`
+/// - Bytes 8-11: format version (u32 LE)
+/// - Bytes 12-19: manifest length (u64 LE)
+/// - Manifest JSON
+/// - Account blob: bincode-encoded `Vec<(Pubkey, Account)>`, including sysvar accounts
+pub struct ForkArchive {
+    pub manifest: ArchiveManifest,
+    pub accounts: Vec<(Pubkey, Account)>,
+}
+
+impl ForkArchive {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let manifest = serde_json::to_vec(&self.manifest)?;
+        let accounts = bincode::serialize(&self.accounts)?;
+
+        let mut bytes = Vec::with_capacity(20 + manifest.len() + accounts.len());
+        bytes.extend_from_slice(ARCHIVE_MAGIC);
+        bytes.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(manifest.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&manifest);
+        bytes.extend_from_slice(&accounts);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 20 || &bytes[..8] != ARCHIVE_MAGIC {
+            return Err(anyhow::anyhow!("Not a fork archive"));
+        }
+
+        let version = u32::from_le_bytes(bytes[8..12].try_into()?);
+        if version != ARCHIVE_VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported archive version {} (expected {})",
+                version,
+                ARCHIVE_VERSION
+            ));
+        }
+
+        let manifest_len = u64::from_le_bytes(bytes[12..20].try_into()?) as usize;
+        let manifest_end = 20usize
+            .checked_add(manifest_len)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| anyhow::anyhow!("Truncated archive manifest"))?;
+
+        let manifest: ArchiveManifest = serde_json::from_slice(&bytes[20..manifest_end])
+            .map_err(|e| anyhow::anyhow!("Invalid archive manifest: {}", e))?;
+        let accounts: Vec<(Pubkey, Account)> = bincode::deserialize(&bytes[manifest_end..])
+            .map_err(|e| anyhow::anyhow!("Invalid archive account blob: {}", e))?;
+
+        Ok(Self { manifest, accounts })
+    }
+}
@@ -0,0 +1,276 @@
+use crate::dependencies::LOADERS;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Approximate bookkeeping overhead of one entry, counted towards the size limit
+const ENTRY_OVERHEAD: usize = 128;
+
+/// Limits of the upstream account cache
+#[derive(Debug, Clone, Copy)]
+pub struct CacheConfig {
+    /// How long fetched accounts are served from the cache. Zero disables caching them.
+    pub max_age: Duration,
+    /// How long accounts owned by a loader (programs and program data) are served from the
+    /// cache. Zero disables caching them.
+    pub program_max_age: Duration,
+    /// Total size of cached accounts; least recently used entries are evicted beyond it
+    pub max_bytes: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::ZERO,
+            program_max_age: Duration::from_secs(600),
+            max_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Cache hit, miss and eviction counts and current size
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+struct Entry {
+    account: Account,
+    /// Upstream slot the account was observed at
+    slot: u64,
+    fetched_at: Instant,
+    last_used: u64,
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        self.account.data.len() + ENTRY_OVERHEAD
+    }
+}
+
+type Key = (String, Pubkey);
+
+#[derive(Default)]
+struct Entries {
+    map: HashMap<Key, Entry>,
+    /// Keys by `last_used`, oldest first
+    lru: BTreeMap<u64, Key>,
+    bytes: usize,
+    /// Increments on every access, used as LRU clock
+    tick: u64,
+}
+
+/// Process-wide cache of accounts fetched from upstream, shared by all forks. Entries are
+/// keyed by upstream and pubkey and remember the slot they were observed at.
+pub struct AccountCache {
+    config: CacheConfig,
+    entries: Mutex<Entries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl Default for AccountCache {
+    fn default() -> Self {
+        Self::new(CacheConfig::default())
+    }
+}
+
+impl AccountCache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            entries: Mutex::new(Entries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Accounts owned by a loader (programs, program data, buffers) are cached as programs
+    fn max_age(&self, account: &Account) -> Duration {
+        if LOADERS.contains(&account.owner) {
+            self.config.program_max_age
+        } else {
+            self.config.max_age
+        }
+    }
+
+    /// Cached account of `upstream` observed at `min_slot` or later and not older than its
+    /// max age. Returns the account and its slot.
+    pub fn get(
+        &self,
+        upstream: &str,
+        pubkey: &Pubkey,
+        min_slot: Option<u64>,
+    ) -> Option<(Account, u64)> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.tick += 1;
+        let tick = entries.tick;
+
+        let key = (upstream.to_string(), *pubkey);
+        let hit = match entries.map.get_mut(&key) {
+            Some(entry)
+                if entry.fetched_at.elapsed() <= self.max_age(&entry.account)
+                    && min_slot.is_none_or(|min_slot| entry.slot >= min_slot) =>
+            {
+                let last_used = std::mem::replace(&mut entry.last_used, tick);
+                let hit = (entry.account.clone(), entry.slot);
+                entries.lru.remove(&last_used);
+                entries.lru.insert(tick, key);
+                Some(hit)
+            }
+            _ => None,
+        };
+
+        let counter = if hit.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        hit
+    }
+
+    /// Store an account fetched from `upstream` at `slot`, replacing older observations
+    pub fn insert(&self, upstream: &str, pubkey: Pubkey, account: &Account, slot: u64) {
+        if self.max_age(account).is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let key = (upstream.to_string(), pubkey);
+        if entries
+            .map
+            .get(&key)
+            .is_some_and(|existing| existing.slot > slot)
+        {
+            return;
+        }
+
+        entries.tick += 1;
+        let entry = Entry {
+            account: account.clone(),
+            slot,
+            fetched_at: Instant::now(),
+            last_used: entries.tick,
+        };
+        if entry.size() > self.config.max_bytes {
+            return;
+        }
+        entries.bytes += entry.size();
+        entries.lru.insert(entry.last_used, key.clone());
+        if let Some(replaced) = entries.map.insert(key, entry) {
+            entries.bytes -= replaced.size();
+            entries.lru.remove(&replaced.last_used);
+        }
+
+        while entries.bytes > self.config.max_bytes {
+            let Some((_, oldest)) = entries.lru.pop_first() else {
+                break;
+            };
+            if let Some(evicted) = entries.map.remove(&oldest) {
+                entries.bytes -= evicted.size();
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        CacheStats {
+            entries: entries.map.len(),
+            bytes: entries.bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UPSTREAM: &str = "primary";
+
+    fn account(len: usize) -> Account {
+        Account {
+            lamports: 1,
+            data: vec![0; len],
+            ..Account::default()
+        }
+    }
+
+    fn cache(max_bytes: usize) -> AccountCache {
+        AccountCache::new(CacheConfig {
+            max_age: Duration::from_secs(60),
+            max_bytes,
+            ..CacheConfig::default()
+        })
+    }
+
+    #[test]
+    fn evicts_least_recently_used_entries() {
+        let cache = cache(3 * (100 + ENTRY_OVERHEAD));
+        let keys: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        for key in &keys[..3] {
+            cache.insert(UPSTREAM, *key, &account(100), 10);
+        }
+        // Reading the first entry makes the second the least recently used
+        assert!(cache.get(UPSTREAM, &keys[0], None).is_some());
+        cache.insert(UPSTREAM, keys[3], &account(100), 10);
+
+        assert!(cache.get(UPSTREAM, &keys[1], None).is_none());
+        for key in [keys[0], keys[2], keys[3]] {
+            assert!(cache.get(UPSTREAM, &key, None).is_some());
+        }
+        let stats = cache.stats();
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.bytes, 3 * (100 + ENTRY_OVERHEAD));
+        assert_eq!(stats.evictions, 1);
+    }
+
+    #[test]
+    fn replacing_an_entry_updates_its_size_and_recency() {
+        let cache = cache(2 * (100 + ENTRY_OVERHEAD));
+        let (first, second, third) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        cache.insert(UPSTREAM, first, &account(100), 10);
+        cache.insert(UPSTREAM, second, &account(100), 10);
+        cache.insert(UPSTREAM, first, &account(50), 11);
+        assert_eq!(cache.stats().bytes, 150 + 2 * ENTRY_OVERHEAD);
+
+        // An older observation does not replace a newer one
+        cache.insert(UPSTREAM, first, &account(100), 9);
+        assert_eq!(cache.get(UPSTREAM, &first, None).unwrap().1, 11);
+
+        // The replaced entry is no longer the oldest, so the untouched one is evicted
+        cache.insert(UPSTREAM, third, &account(100), 10);
+        assert!(cache.get(UPSTREAM, &second, None).is_none());
+        assert!(cache.get(UPSTREAM, &first, None).is_some());
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn skips_entries_below_min_slot() {
+        let cache = cache(1024 * 1024);
+        let key = Pubkey::new_unique();
+        cache.insert(UPSTREAM, key, &account(10), 100);
+
+        assert_eq!(cache.get(UPSTREAM, &key, Some(100)).unwrap().1, 100);
+        assert!(cache.get(UPSTREAM, &key, Some(101)).is_none());
+        assert!(cache.get("secondary", &key, None).is_none());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+    }
+}
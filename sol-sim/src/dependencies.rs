@@ -0,0 +1,164 @@
+use crate::InclusionReason;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+/// BPF loaders whose programs LiteSVM can load: v1 (deprecated), v2, v3 (upgradeable) and v4
+pub const LOADERS: [Pubkey; 4] = [
+    solana_sdk::pubkey!("BPFLoader1111111111111111111111111111111111"),
+    solana_sdk::pubkey!("BPFLoader2111111111111111111111111111111111"),
+    BPF_LOADER_UPGRADEABLE,
+    solana_sdk::pubkey!("LoaderV411111111111111111111111111111111111"),
+];
+
+const BPF_LOADER_UPGRADEABLE: Pubkey =
+    solana_sdk::pubkey!("BPFLoaderUpgradeab1e11111111111111111111111");
+
+/// `UpgradeableLoaderState::Program` discriminator; the programdata address follows it
+const UPGRADEABLE_PROGRAM_TAG: [u8; 4] = [2, 0, 0, 0];
+
+/// `UpgradeableLoaderState::ProgramData` discriminator
+const UPGRADEABLE_PROGRAM_DATA_TAG: [u8; 4] = [3, 0, 0, 0];
+
+/// Address lookup table program
+pub const ADDRESS_LOOKUP_TABLE_PROGRAM: Pubkey =
+    solana_sdk::pubkey!("AddressLookupTab1e1111111111111111111111111");
+
+/// Size of the `LookupTableMeta` header; addresses follow as 32-byte keys
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+const TOKEN_PROGRAM: Pubkey = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+const TOKEN_2022_PROGRAM: Pubkey =
+    solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Size of a token account without extensions; the mint is its first field
+const TOKEN_ACCOUNT_SIZE: usize = 165;
+
+/// Size of a multisig account. Token-2022 pads accounts with extensions to never match it.
+const MULTISIG_SIZE: usize = 355;
+
+/// Token-2022 `AccountType`, stored right after the base token account layout
+const ACCOUNT_TYPE_MINT: u8 = 1;
+const ACCOUNT_TYPE_ACCOUNT: u8 = 2;
+
+/// Token-2022 `ExtensionType::TransferHook`: authority and program id, 32 bytes each
+const TRANSFER_HOOK_EXTENSION: u16 = 14;
+
+/// Seed of the account a transfer hook program keeps its extra account metas in
+const EXTRA_ACCOUNT_METAS_SEED: &[u8] = b"extra-account-metas";
+
+/// Whether `account` is a program one of the BPF loaders can execute
+pub fn is_program(account: &Account) -> bool {
+    account.executable && LOADERS.contains(&account.owner)
+}
+
+/// Whether `account` is an upgradeable program's programdata account, which must be loaded
+/// before the program
+pub fn is_program_data(account: &Account) -> bool {
+    account.owner == BPF_LOADER_UPGRADEABLE
+        && account.data.get(..4) == Some(&UPGRADEABLE_PROGRAM_DATA_TAG[..])
+}
+
+/// Accounts `account` needs to work in a fork besides its owner, and why:
+/// - an upgradeable program's programdata account
+/// - the addresses stored in an address lookup table
+/// - the mint of a token account
+/// - the transfer hook program of a Token-2022 mint and its extra account metas
+///
+/// Programs on loader v1, v2 and v4 keep their executable data in the program account.
+pub fn account_dependencies(pubkey: &Pubkey, account: &Account) -> Vec<(Pubkey, InclusionReason)> {
+    let data = &account.data;
+    let with_reason =
+        |pubkeys: Vec<Pubkey>, reason| pubkeys.into_iter().map(|pubkey| (pubkey, reason)).collect();
+    match account.owner {
+        BPF_LOADER_UPGRADEABLE if data.get(..4) == Some(&UPGRADEABLE_PROGRAM_TAG[..]) => {
+            with_reason(
+                read_pubkey(data, 4).into_iter().collect(),
+                InclusionReason::ProgramData,
+            )
+        }
+        ADDRESS_LOOKUP_TABLE_PROGRAM => with_reason(
+            lookup_table_entries(account).unwrap_or_default(),
+            InclusionReason::LookupTableEntry,
+        ),
+        TOKEN_PROGRAM if data.len() == TOKEN_ACCOUNT_SIZE => with_reason(
+            read_pubkey(data, 0).into_iter().collect(),
+            InclusionReason::Mint,
+        ),
+        TOKEN_2022_PROGRAM => match token_2022_account_type(data) {
+            Some(ACCOUNT_TYPE_ACCOUNT) => with_reason(
+                read_pubkey(data, 0).into_iter().collect(),
+                InclusionReason::Mint,
+            ),
+            Some(ACCOUNT_TYPE_MINT) => transfer_hook_dependencies(pubkey, data),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Addresses stored in an address lookup table, or `None` if `table` is not one
+pub fn lookup_table_entries(table: &Account) -> Option<Vec<Pubkey>> {
+    if table.owner != ADDRESS_LOOKUP_TABLE_PROGRAM {
+        return None;
+    }
+    Some(
+        table
+            .data
+            .get(LOOKUP_TABLE_META_SIZE..)
+            .unwrap_or_default()
+            .chunks_exact(32)
+            .map(|bytes| Pubkey::try_from(bytes).expect("32-byte chunk"))
+            .collect(),
+    )
+}
+
+/// Address of a program's Anchor IDL account
+pub fn idl_address(program_id: &Pubkey) -> Pubkey {
+    let (base, _) = Pubkey::find_program_address(&[], program_id);
+    Pubkey::create_with_seed(&base, "anchor:idl", program_id).expect("seed is short enough")
+}
+
+/// Token-2022 account type: plain token accounts are exactly [`TOKEN_ACCOUNT_SIZE`] bytes,
+/// accounts with extensions store their type right after the base layout
+fn token_2022_account_type(data: &[u8]) -> Option<u8> {
+    match data.len() {
+        TOKEN_ACCOUNT_SIZE => Some(ACCOUNT_TYPE_ACCOUNT),
+        MULTISIG_SIZE => None,
+        len if len > TOKEN_ACCOUNT_SIZE => Some(data[TOKEN_ACCOUNT_SIZE]),
+        _ => None,
+    }
+}
+
+/// Transfer hook program of a Token-2022 mint with the `TransferHook` extension, and the
+/// account it resolves extra accounts from
+fn transfer_hook_dependencies(mint: &Pubkey, data: &[u8]) -> Vec<(Pubkey, InclusionReason)> {
+    // Extensions are TLV entries: u16 type, u16 length, value
+    let mut offset = TOKEN_ACCOUNT_SIZE + 1;
+    while let Some(header) = data.get(offset..offset + 4) {
+        let extension = u16::from_le_bytes([header[0], header[1]]);
+        let len = u16::from_le_bytes([header[2], header[3]]) as usize;
+        let value = offset + 4;
+        if extension == TRANSFER_HOOK_EXTENSION {
+            return match read_pubkey(data, value + 32) {
+                Some(program_id) if program_id != Pubkey::default() => {
+                    let (extra_metas, _) = Pubkey::find_program_address(
+                        &[EXTRA_ACCOUNT_METAS_SEED, mint.as_ref()],
+                        &program_id,
+                    );
+                    vec![
+                        (program_id, InclusionReason::TransferHook),
+                        (extra_metas, InclusionReason::ExtraAccountMetas),
+                    ]
+                }
+                _ => Vec::new(),
+            };
+        }
+        offset = value + len;
+    }
+    Vec::new()
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> Option<Pubkey> {
+    data.get(offset..offset + 32)
+        .map(|bytes| Pubkey::try_from(bytes).expect("32-byte slice"))
+}
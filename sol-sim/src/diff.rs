@@ -0,0 +1,168 @@
+use crate::AccountData;
+use anyhow::Result;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    pubkey::Pubkey,
+};
+use std::collections::HashMap;
+
+/// Differing byte runs separated by fewer unchanged bytes than this are merged into one range
+const MERGE_GAP: usize = 8;
+
+/// Account changes between two states of a fork. Sysvar accounts are not included.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForkDiff {
+    pub created: Vec<CreatedAccount>,
+    pub deleted: Vec<String>,
+    pub modified: Vec<ModifiedAccount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedAccount {
+    pub pubkey: String,
+    pub account: AccountData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModifiedAccount {
+    pub pubkey: String,
+    pub lamports: ValueChange<u64>,
+    #[serde(rename = "lamportDelta")]
+    pub lamport_delta: i128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<ValueChange<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub executable: Option<ValueChange<bool>>,
+    #[serde(rename = "dataLen", skip_serializing_if = "Option::is_none")]
+    pub data_len: Option<ValueChange<usize>>,
+    #[serde(rename = "dataChanges", default)]
+    pub data_changes: Vec<DataChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueChange<T> {
+    pub before: T,
+    pub after: T,
+}
+
+/// Changed byte range; `before` and `after` are base64. `before` is shorter than `after`
+/// when the range extends past the end of the original data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataChange {
+    pub offset: usize,
+    pub before: String,
+    pub after: String,
+}
+
+/// Compare two account sets, ordering each list by pubkey
+pub fn diff_accounts(
+    before: &HashMap<Pubkey, AccountSharedData>,
+    after: &HashMap<Pubkey, AccountSharedData>,
+) -> ForkDiff {
+    let mut diff = ForkDiff::default();
+
+    let mut pubkeys: Vec<&Pubkey> = before.keys().chain(after.keys()).collect();
+    pubkeys.sort();
+    pubkeys.dedup();
+
+    for pubkey in pubkeys {
+        let old = before.get(pubkey).map(|a| Account::from(a.clone()));
+        let new = after.get(pubkey).map(|a| Account::from(a.clone()));
+        if [&old, &new]
+            .into_iter()
+            .flatten()
+            .any(|account| account.owner == solana_sdk::sysvar::ID)
+        {
+            continue;
+        }
+
+        match (old, new) {
+            (None, Some(new)) => diff.created.push(CreatedAccount {
+                pubkey: pubkey.to_string(),
+                account: AccountData::from_account(&new),
+            }),
+            (Some(_), None) => diff.deleted.push(pubkey.to_string()),
+            (Some(old), Some(new)) if old != new => {
+                diff.modified.push(modified_account(pubkey, &old, &new))
+            }
+            _ => {}
+        }
+    }
+
+    diff
+}
+
+fn modified_account(pubkey: &Pubkey, old: &Account, new: &Account) -> ModifiedAccount {
+    ModifiedAccount {
+        pubkey: pubkey.to_string(),
+        lamports: ValueChange {
+            before: old.lamports,
+            after: new.lamports,
+        },
+        lamport_delta: new.lamports as i128 - old.lamports as i128,
+        owner: change(old.owner.to_string(), new.owner.to_string()),
+        executable: change(old.executable, new.executable),
+        data_len: change(old.data.len(), new.data.len()),
+        data_changes: data_changes(&old.data, &new.data),
+    }
+}
+
+fn change<T: PartialEq>(before: T, after: T) -> Option<ValueChange<T>> {
+    (before != after).then_some(ValueChange { before, after })
+}
+
+/// Byte ranges of `new` that differ from `old`, including bytes appended past the end of `old`
+fn data_changes(old: &[u8], new: &[u8]) -> Vec<DataChange> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in (0..new.len()).filter(|i| old.get(*i) != Some(&new[*i])) {
+        match ranges.last_mut() {
+            Some((_, end)) if i - *end < MERGE_GAP => *end = i + 1,
+            _ => ranges.push((i, i + 1)),
+        }
+    }
+
+    let encode = |bytes: &[u8]| base64::engine::general_purpose::STANDARD.encode(bytes);
+    ranges
+        .into_iter()
+        .map(|(start, end)| DataChange {
+            offset: start,
+            before: encode(&old[start.min(old.len())..end.min(old.len())]),
+            after: encode(&new[start..end]),
+        })
+        .collect()
+}
+
+/// Apply a modification to an account: lamports, owner and executable are set to their
+/// `after` values, the data is resized to the `after` length and changed ranges are written.
+pub fn apply_modification(account: &mut Account, change: &ModifiedAccount) -> Result<()> {
+    account.lamports = change.lamports.after;
+    if let Some(owner) = &change.owner {
+        account.owner = owner.after.parse()?;
+    }
+    if let Some(executable) = &change.executable {
+        account.executable = executable.after;
+    }
+    if let Some(data_len) = &change.data_len {
+        account.data.resize(data_len.after, 0);
+    }
+
+    for range in &change.data_changes {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(&range.after)?;
+        let end = range
+            .offset
+            .checked_add(bytes.len())
+            .filter(|end| *end <= account.data.len())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Data change at offset {} exceeds account {} data length {}",
+                    range.offset,
+                    change.pubkey,
+                    account.data.len()
+                )
+            })?;
+        account.data[range.offset..end].copy_from_slice(&bytes);
+    }
+    Ok(())
+}
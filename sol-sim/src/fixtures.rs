@@ -0,0 +1,295 @@
+use crate::ProgramAccountsFilter;
+use anyhow::Result;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_sdk::{account::Account, hash::Hash, pubkey::Pubkey, rent::Rent};
+use solana_sysvar::epoch_schedule::EpochSchedule;
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::info;
+
+/// Loader that programs read from `.so` files are deployed with
+const BPF_LOADER: Pubkey = solana_sdk::pubkey!("BPFLoader2111111111111111111111111111111111");
+
+/// File in a fixture directory recording the slot the accounts were dumped at
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Account file in the format of `solana account --output json`
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountFile {
+    pubkey: String,
+    account: AccountFileData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountFileData {
+    lamports: u64,
+    /// `[data, encoding]`; only `base64` is supported
+    data: (String, String),
+    owner: String,
+    executable: bool,
+    #[serde(rename = "rentEpoch")]
+    rent_epoch: u64,
+    #[serde(default)]
+    space: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    slot: u64,
+    /// Unix timestamp of the block at `slot`
+    #[serde(rename = "blockTime", default, skip_serializing_if = "Option::is_none")]
+    block_time: Option<i64>,
+}
+
+/// Accounts read from a fixture directory, served in place of an upstream RPC.
+///
+/// The directory holds `*.json` account files as written by `solana account --output json`
+/// (and read by `solana-test-validator --account`), and `<program_id>.so` program binaries,
+/// which are deployed with the BPF loader. `manifest.json` optionally records the slot and its
+/// block time. Epochs follow mainnet's epoch schedule.
+pub struct Fixtures {
+    accounts: HashMap<Pubkey, Account>,
+    slot: u64,
+    block_time: Option<i64>,
+}
+
+impl Fixtures {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut accounts = HashMap::new();
+        let mut manifest = Manifest::default();
+
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to read fixtures {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            let invalid =
+                |e: anyhow::Error| anyhow::anyhow!("Invalid fixture {}: {}", path.display(), e);
+
+            if name == MANIFEST_FILE {
+                manifest = serde_json::from_slice(&std::fs::read(&path)?)
+                    .map_err(|e| invalid(e.into()))?;
+                continue;
+            }
+            match path.extension().and_then(|e| e.to_str()) {
+                Some("json") => {
+                    let (pubkey, account) =
+                        parse_account_file(&std::fs::read(&path)?).map_err(invalid)?;
+                    accounts.insert(pubkey, account);
+                }
+                Some("so") => {
+                    let pubkey: Pubkey = path
+                        .file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .unwrap_or_default()
+                        .parse()
+                        .map_err(|_| {
+                            invalid(anyhow::anyhow!("file name must be <program_id>.so"))
+                        })?;
+                    accounts.insert(pubkey, program_account(std::fs::read(&path)?));
+                }
+                _ => {}
+            }
+        }
+
+        info!(
+            "Loaded {} fixture account(s) from {}",
+            accounts.len(),
+            dir.display()
+        );
+        Ok(Self {
+            accounts,
+            slot: manifest.slot,
+            block_time: manifest.block_time,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// Answer the JSON-RPC methods account fetching uses, as an upstream RPC would
+    pub fn call(&self, method: &str, params: &serde_json::Value) -> Result<serde_json::Value> {
+        let context = json!({"slot": self.slot});
+        match method {
+            "getMultipleAccounts" => {
+                let value = params[0]
+                    .as_array()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid params: expected pubkey array"))?
+                    .iter()
+                    .map(|pubkey| self.account_value(pubkey))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(json!({"context": context, "value": value}))
+            }
+            "getProgramAccounts" => {
+                let value = self.program_accounts(params)?;
+                if params[1]["withContext"].as_bool().unwrap_or(false) {
+                    Ok(json!({"context": context, "value": value}))
+                } else {
+                    Ok(value)
+                }
+            }
+            "getAccountInfo" => {
+                Ok(json!({"context": context, "value": self.account_value(&params[0])?}))
+            }
+            "getLatestBlockhash" => Ok(json!({
+                "context": context,
+                "value": {"blockhash": Hash::default().to_string(), "lastValidBlockHeight": 0}
+            })),
+            "getSlot" => Ok(json!(self.slot)),
+            "getBlockTime" => Ok(json!(self.block_time.filter(|_| params[0] == self.slot))),
+            "getEpochSchedule" => Ok(serde_json::to_value(EpochSchedule::without_warmup())?),
+            "getEpochInfo" => {
+                let (epoch, slot_index) =
+                    EpochSchedule::without_warmup().get_epoch_and_slot_index(self.slot);
+                Ok(json!({
+                    "absoluteSlot": self.slot,
+                    "blockHeight": self.slot,
+                    "epoch": epoch,
+                    "slotIndex": slot_index,
+                    "slotsInEpoch": EpochSchedule::without_warmup().get_slots_in_epoch(epoch),
+                    "transactionCount": null
+                }))
+            }
+            _ => Err(anyhow::anyhow!("{} is not available from fixtures", method)),
+        }
+    }
+
+    fn account_value(&self, pubkey: &serde_json::Value) -> Result<serde_json::Value> {
+        let pubkey: Pubkey = pubkey
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid pubkey"))?
+            .parse()?;
+        Ok(self
+            .accounts
+            .get(&pubkey)
+            .map(account_json)
+            .unwrap_or(serde_json::Value::Null))
+    }
+
+    /// Accounts owned by the program in `params[0]` matching all filters, sorted by pubkey
+    fn program_accounts(&self, params: &serde_json::Value) -> Result<serde_json::Value> {
+        let program_id: Pubkey = params[0]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid program id"))?
+            .parse()?;
+        let filters: Vec<ProgramAccountsFilter> = match &params[1]["filters"] {
+            serde_json::Value::Null => Vec::new(),
+            filters => serde_json::from_value(filters.clone())?,
+        };
+
+        let mut matches = Vec::new();
+        for (pubkey, account) in &self.accounts {
+            if account.owner != program_id {
+                continue;
+            }
+            let mut matched = true;
+            for filter in &filters {
+                matched &= filter.matches(&account.data)?;
+            }
+            if matched {
+                matches.push((pubkey, account));
+            }
+        }
+        matches.sort_by_key(|(pubkey, _)| **pubkey);
+
+        Ok(matches
+            .into_iter()
+            .map(|(pubkey, account)| {
+                json!({"pubkey": pubkey.to_string(), "account": account_json(account)})
+            })
+            .collect())
+    }
+}
+
+/// Account in the RPC's base64 JSON encoding
+fn account_json(account: &Account) -> serde_json::Value {
+    json!({
+        "lamports": account.lamports,
+        "data": [base64::engine::general_purpose::STANDARD.encode(&account.data), "base64"],
+        "owner": account.owner.to_string(),
+        "executable": account.executable,
+        "rentEpoch": account.rent_epoch,
+        "space": account.data.len(),
+    })
+}
+
+fn parse_account_file(bytes: &[u8]) -> Result<(Pubkey, Account)> {
+    let file: AccountFile = serde_json::from_slice(bytes)?;
+    let (data, encoding) = &file.account.data;
+    if encoding != "base64" {
+        return Err(anyhow::anyhow!(
+            "unsupported data encoding '{}', expected base64",
+            encoding
+        ));
+    }
+    let account = Account {
+        lamports: file.account.lamports,
+        data: base64::engine::general_purpose::STANDARD.decode(data)?,
+        owner: file.account.owner.parse()?,
+        executable: file.account.executable,
+        rent_epoch: file.account.rent_epoch,
+    };
+    Ok((file.pubkey.parse()?, account))
+}
+
+/// Rent-exempt executable account holding a program binary
+fn program_account(elf: Vec<u8>) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(elf.len()),
+        data: elf,
+        owner: BPF_LOADER,
+        executable: true,
+        rent_epoch: 0,
+    }
+}
+
+/// Write accounts to `dir` as `<pubkey>.json` account files, with the slot they were fetched at
+/// and its block time
+pub fn write(
+    dir: &Path,
+    accounts: &[(Pubkey, Account)],
+    slot: Option<u64>,
+    block_time: Option<i64>,
+) -> Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for (pubkey, account) in accounts {
+        let file = AccountFile {
+            pubkey: pubkey.to_string(),
+            account: AccountFileData {
+                lamports: account.lamports,
+                data: (
+                    base64::engine::general_purpose::STANDARD.encode(&account.data),
+                    "base64".to_string(),
+                ),
+                owner: account.owner.to_string(),
+                executable: account.executable,
+                rent_epoch: account.rent_epoch,
+                space: Some(account.data.len() as u64),
+            },
+        };
+        std::fs::write(
+            dir.join(format!("{}.json", pubkey)),
+            serde_json::to_vec_pretty(&file)?,
+        )?;
+    }
+
+    let manifest = Manifest {
+        slot: slot.unwrap_or_default(),
+        block_time,
+    };
+    std::fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )?;
+    Ok(())
+}
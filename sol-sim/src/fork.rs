@@ -1,46 +1,1571 @@
 use crate::{
-    AccountData, ForkId, ForkInfo, JsonRpcError, JsonRpcRequest, JsonRpcResponse, Storage,
+    archive::{ArchiveManifest, ForkArchive, ARCHIVE_VERSION},
+    cache::{AccountCache, CacheStats},
+    dependencies,
+    diff::{self, ForkDiff},
+    oracle::{self, PriceOverride},
+    upstream::{RpcUrlNotAllowed, UpstreamClient, Upstreams},
+    AccountData, AccountFailure, BlockProduction, ChainSync, Commitment, CreateForkRequest,
+    ExtendForkRequest, FetchLimits, FetchReport, ForkEvent, ForkId, ForkInfo, InclusionReason,
+    JsonRpcError, JsonRpcRequest, JsonRpcResponse, OraclePriceParams, PlannedAccount,
+    ProgramAccountsFilter, ProgramAccountsQuery, RefreshPolicy, Storage, TransactionRecord,
+    DEFAULT_TTL_MINUTES,
 };
 use anyhow::Result;
 use base64::Engine;
+use futures::{stream, StreamExt, TryStreamExt};
 use litesvm::LiteSVM;
 use serde_json::json;
-use solana_sdk::{account::Account, pubkey::Pubkey, transaction::Transaction};
-use solana_sysvar::clock::Clock;
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use solana_nonce::{
+    state::{DurableNonce, State as NonceState},
+    versions::Versions as NonceVersions,
+};
+use solana_sdk::{
+    account::Account,
+    hash::{self, Hash},
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use solana_sysvar::{clock::Clock, epoch_schedule::EpochSchedule, slot_hashes::SlotHashes};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Mutex, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
-/// Manages all active forks in-memory
+/// Wall-clock time represented by one slot
+const SLOT_DURATION_MS: i64 = 400;
+
+/// Shortest accepted interval for interval block production
+const MIN_BLOCK_INTERVAL_MS: u64 = 10;
+
+/// Blockhashes a fork accepts transactions signed against, as many as the `RecentBlockhashes`
+/// sysvar holds
+const MAX_RECENT_BLOCKHASHES: usize = 150;
+
+/// Fee per signature recorded in the `RecentBlockhashes` sysvar, liteSVM's default
+const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+/// Least time between saves of blocks produced by the interval producer
+const PRODUCER_SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most accounts upstream RPC nodes accept in one `getMultipleAccounts` request
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Default number of `getMultipleAccounts` requests in flight per fetch
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+
+/// Passes over the account set before giving up on all batches agreeing on one slot
+const MAX_SLOT_ATTEMPTS: usize = 5;
+
+/// How long an evicted fork's metadata is kept, so requests for it fail with `ForkExpired`
+/// rather than as if the fork never existed
+const EXPIRED_RETENTION: chrono::Duration = chrono::Duration::hours(24);
+
+/// Fork events buffered for subscribers that fall behind
+const EVENT_CAPACITY: usize = 256;
+
+/// Most accounts a `getProgramAccounts` query may match unless it sets `maxAccounts`
+const DEFAULT_MAX_PROGRAM_ACCOUNTS: usize = 10_000;
+
+/// Upstream query settings for fetching accounts
+#[derive(Debug, Clone, Copy, Default)]
+struct FetchOptions {
+    commitment: Commitment,
+    min_context_slot: Option<u64>,
+    /// Bypass the account cache
+    fresh: bool,
+    /// Also fetch the Anchor IDL account of every program
+    idl: bool,
+}
+
+impl FetchOptions {
+    /// Options for fetches into an existing fork: its commitment and IDL setting, and its source
+    /// slot as the minimum context slot so accounts are never older than the rest of the fork
+    fn for_fork(info: &ForkInfo) -> Self {
+        Self {
+            commitment: info.commitment,
+            min_context_slot: info.source_slot,
+            fresh: false,
+            idl: info.idl,
+        }
+    }
+}
+
+/// Why an account was fetched and how far it is from the accounts a fetch started from
+#[derive(Debug, Clone, Copy)]
+struct Inclusion {
+    reason: InclusionReason,
+    dependency_of: Option<Pubkey>,
+    depth: usize,
+}
+
+/// Progress of a recursive account fetch
+struct FetchState {
+    upstream: UpstreamClient,
+    options: FetchOptions,
+    limits: FetchLimits,
+    /// Newest slot among loader-owned accounts served from the cache. Programs are cached for
+    /// long and rarely change, so they only have to be no newer than the fresh batches.
+    cached_slot: Option<u64>,
+    /// Accounts fetched from upstream rather than the cache. A refetch at a pinned slot
+    /// must not serve them from the cache.
+    uncached: HashSet<String>,
+    accounts: Vec<(Pubkey, Account)>,
+    processed: HashSet<String>,
+    /// `context.slot` of every upstream response and the slot every other cached account was
+    /// observed at, so those cache hits must agree with fresh batches
+    slots: Vec<u64>,
+    /// Total data size of `accounts`
+    bytes: u64,
+    /// Accounts upstream returned `null` for
+    missing: Vec<String>,
+    /// Accounts upstream returned that could not be decoded
+    failed: Vec<AccountFailure>,
+    /// Inclusion of every account reached, fetched or not. The first reason recorded wins.
+    inclusions: HashMap<String, Inclusion>,
+}
+
+impl FetchState {
+    fn new(upstream: UpstreamClient, options: FetchOptions, limits: FetchLimits) -> Self {
+        Self {
+            upstream,
+            options,
+            limits,
+            cached_slot: None,
+            uncached: HashSet::new(),
+            accounts: Vec::new(),
+            processed: HashSet::new(),
+            slots: Vec::new(),
+            bytes: 0,
+            missing: Vec::new(),
+            failed: Vec::new(),
+            inclusions: HashMap::new(),
+        }
+    }
+
+    /// Record that `pubkey` is fetched for `inclusion`, unless it already was for another reason
+    fn include(&mut self, pubkey: &str, inclusion: Inclusion) {
+        self.inclusions
+            .entry(pubkey.to_string())
+            .or_insert(inclusion);
+    }
+
+    fn depth(&self, pubkey: &str) -> usize {
+        self.inclusions
+            .get(pubkey)
+            .map(|inclusion| inclusion.depth)
+            .unwrap_or_default()
+    }
+
+    /// Whether the dependencies of an account at `depth` are followed
+    fn follows_dependencies(&self, depth: usize) -> bool {
+        self.limits
+            .max_depth
+            .is_none_or(|max_depth| depth < max_depth)
+    }
+}
+
+/// Accounts fetched from upstream, all observed at `slot`
+struct FetchedAccounts {
+    accounts: Vec<(Pubkey, Account)>,
+    slot: Option<u64>,
+    /// Accounts matched by program account queries
+    matched: Vec<String>,
+    inclusions: HashMap<String, Inclusion>,
+    /// Accounts upstream returned `null` for, dependencies included
+    missing: Vec<String>,
+    failed: Vec<AccountFailure>,
+}
+
+impl FetchedAccounts {
+    /// Loaded accounts, requested accounts that don't exist upstream and decode failures
+    fn report(&self) -> FetchReport {
+        FetchReport {
+            loaded: self
+                .accounts
+                .iter()
+                .map(|(pubkey, _)| pubkey.to_string())
+                .collect(),
+            missing: self
+                .missing
+                .iter()
+                .filter(|pubkey| {
+                    self.inclusions
+                        .get(*pubkey)
+                        .is_none_or(|inclusion| inclusion.dependency_of.is_none())
+                })
+                .cloned()
+                .collect(),
+            failed: self.failed.clone(),
+        }
+    }
+
+    /// Fail if any account upstream returned could not be decoded
+    fn ensure_decoded(&self) -> Result<()> {
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        let failures: Vec<String> = self
+            .failed
+            .iter()
+            .map(|failure| format!("{}: {}", failure.pubkey, failure.error))
+            .collect();
+        Err(anyhow::anyhow!(
+            "Failed to decode upstream account(s): {}",
+            failures.join(", ")
+        ))
+    }
+}
+
+/// Chain context a fork starts from
+struct ChainContext {
+    /// Upstream slot the fork starts at; `None` if upstream was unreachable
+    slot: Option<u64>,
+    /// Upstream's latest blockhash, which becomes the fork's blockhash
+    blockhash: Option<Hash>,
+    sync: ChainSync,
+}
+
+/// A fork creation request resolved against its upstream
+struct ForkSource {
+    cluster: Option<String>,
+    upstream_url: Option<String>,
+    upstream: UpstreamClient,
+    options: FetchOptions,
+    /// Requested accounts followed by the accounts of the transaction
+    account_pubkeys: Vec<String>,
+    transaction: Option<VersionedTransaction>,
+}
+
+/// Saved copy of a fork's complete state
+#[derive(Clone)]
+struct ForkSnapshot {
+    id: u64,
+    svm: LiteSVM,
+    pending_clock_ms: i64,
+    recent_blockhashes: VecDeque<Hash>,
+    transactions: Vec<TransactionRecord>,
+}
+
+/// Fork state changed since the last save
+struct PendingSave {
+    /// Manifest without the transaction history
+    manifest: ArchiveManifest,
+    accounts: Vec<(Pubkey, Option<Account>)>,
+    /// Transactions executed since the last save, or all of them with `replace`
+    transactions: Vec<TransactionRecord>,
+    replace: bool,
+    /// State at fork creation, saved once
+    origin: Option<ForkArchive>,
+}
+
+/// A fork's liteSVM instance together with its block production state
+pub struct Fork {
+    pub svm: LiteSVM,
+    block_production: BlockProduction,
+    /// Milliseconds of slot time not yet reflected in `Clock::unix_timestamp`
+    pending_clock_ms: i64,
+    /// Blockhashes transactions may be signed against, oldest first. liteSVM only knows its
+    /// own latest blockhash and can't adopt another, so the fork keeps the window and checks
+    /// transaction age itself.
+    recent_blockhashes: VecDeque<Hash>,
+    transactions: Vec<TransactionRecord>,
+    /// Snapshots in creation order, so ids are ascending
+    snapshots: Vec<ForkSnapshot>,
+    next_snapshot_id: u64,
+    /// Handle to this fork, used by the interval block producer
+    handle: Weak<Mutex<Fork>>,
+    producer: Option<JoinHandle<()>>,
+    /// State captured when the fork was created, the base for diffs and resets
+    origin: ForkSnapshot,
+    /// Accounts requested when the fork was created
+    source_accounts: Vec<String>,
+    /// Fetch accounts missing from the fork from upstream on first use
+    lazy: bool,
+    /// Keys confirmed not to exist upstream, so lazy fetching skips them
+    missing_accounts: HashSet<Pubkey>,
+    /// Accounts changed since the state was last saved
+    changed_accounts: HashSet<Pubkey>,
+    /// Whether anything changed since the state was last saved
+    unsaved: bool,
+    /// Whether the next save must rewrite the whole state, e.g. after a revert
+    replaced: bool,
+    /// Whether the origin state has not been saved yet
+    origin_unsaved: bool,
+    /// Number of leading `transactions` already saved
+    saved_transactions: usize,
+    /// Storage the interval producer saves the fork to, with a durable backend
+    storage: Option<(ForkId, Arc<dyn Storage>)>,
+    /// When the interval producer last saved the fork
+    producer_saved_at: Option<Instant>,
+}
+
+impl Fork {
+    fn new(svm: LiteSVM, block_production: BlockProduction) -> Result<Arc<Mutex<Self>>> {
+        let svm = svm.with_blockhash_check(false);
+        let recent_blockhashes = VecDeque::from([svm.latest_blockhash()]);
+        let origin = ForkSnapshot {
+            id: 0,
+            svm: svm.clone(),
+            pending_clock_ms: 0,
+            recent_blockhashes: recent_blockhashes.clone(),
+            transactions: Vec::new(),
+        };
+        let fork = Arc::new_cyclic(|handle| {
+            Mutex::new(Self {
+                svm,
+                block_production: BlockProduction::PerTransaction,
+                pending_clock_ms: 0,
+                recent_blockhashes,
+                transactions: Vec::new(),
+                snapshots: Vec::new(),
+                next_snapshot_id: 1,
+                handle: handle.clone(),
+                producer: None,
+                origin,
+                source_accounts: Vec::new(),
+                lazy: false,
+                missing_accounts: HashSet::new(),
+                changed_accounts: HashSet::new(),
+                unsaved: true,
+                replaced: true,
+                origin_unsaved: true,
+                saved_transactions: 0,
+                storage: None,
+                producer_saved_at: None,
+            })
+        });
+        fork.try_lock()?.set_block_production(block_production)?;
+        Ok(fork)
+    }
+
+    /// Switch block production mode, starting or stopping the interval producer
+    pub fn set_block_production(&mut self, mode: BlockProduction) -> Result<()> {
+        if let BlockProduction::Interval { interval_ms } = mode {
+            if interval_ms < MIN_BLOCK_INTERVAL_MS {
+                return Err(anyhow::anyhow!(
+                    "intervalMs must be at least {}",
+                    MIN_BLOCK_INTERVAL_MS
+                ));
+            }
+        }
+
+        if let Some(producer) = self.producer.take() {
+            producer.abort();
+        }
+        if let BlockProduction::Interval { interval_ms } = mode {
+            self.producer = Some(Self::spawn_producer(self.handle.clone(), interval_ms));
+        }
+        self.block_production = mode;
+        self.unsaved = true;
+        Ok(())
+    }
+
+    /// Set an account, recording it for the next save
+    pub fn set_account(&mut self, pubkey: Pubkey, account: Account) -> Result<()> {
+        self.svm.set_account(pubkey, account)?;
+        self.mark_changed([pubkey]);
+        Ok(())
+    }
+
+    fn mark_changed(&mut self, pubkeys: impl IntoIterator<Item = Pubkey>) {
+        self.changed_accounts.extend(pubkeys);
+        self.unsaved = true;
+    }
+
+    fn spawn_producer(handle: Weak<Mutex<Fork>>, interval_ms: u64) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_millis(interval_ms));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                // Stop once the fork has been deleted
+                let Some(fork) = handle.upgrade() else { break };
+                let mut fork = fork.lock().await;
+                fork.produce_blocks(1);
+
+                let due = fork
+                    .producer_saved_at
+                    .is_none_or(|saved_at| saved_at.elapsed() >= PRODUCER_SAVE_INTERVAL);
+                if let (true, Some((fork_id, storage))) = (due, fork.storage.clone()) {
+                    fork.producer_saved_at = Some(Instant::now());
+                    persist_fork(&*storage, &fork_id, &mut fork).await;
+                }
+            }
+        })
+    }
+
+    /// Advance the slot by `slots`, moving the clock forward by the matching slot time
+    pub fn advance_slots(&mut self, slots: u64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.slot += slots;
+        self.pending_clock_ms += slots as i64 * SLOT_DURATION_MS;
+        clock.unix_timestamp += self.pending_clock_ms / 1000;
+        self.pending_clock_ms %= 1000;
+        self.svm.set_sysvar::<Clock>(&clock);
+        self.unsaved = true;
+    }
+
+    /// Produce `blocks` new blocks: advance slot and clock, then roll the blockhash
+    pub fn produce_blocks(&mut self, blocks: u64) {
+        self.advance_slots(blocks);
+        self.expire_blockhash();
+    }
+
+    /// Blockhash new transactions are signed against
+    pub fn latest_blockhash(&self) -> Hash {
+        *self
+            .recent_blockhashes
+            .back()
+            .expect("a fork always has a blockhash")
+    }
+
+    /// Roll the blockhash, the same way liteSVM does
+    fn expire_blockhash(&mut self) {
+        let blockhash = hash::hash(self.latest_blockhash().as_ref());
+        let mut blockhashes = std::mem::take(&mut self.recent_blockhashes);
+        blockhashes.push_back(blockhash);
+        self.set_recent_blockhashes(blockhashes);
+    }
+
+    /// Use `blockhash` as the latest blockhash, dropping the ones before it
+    fn adopt_blockhash(&mut self, blockhash: Hash) {
+        self.set_recent_blockhashes(VecDeque::from([blockhash]));
+    }
+
+    /// Replace the blockhash window, keeping the newest `MAX_RECENT_BLOCKHASHES`, and update
+    /// the `RecentBlockhashes` sysvar to match
+    #[allow(deprecated)]
+    fn set_recent_blockhashes(&mut self, mut blockhashes: VecDeque<Hash>) {
+        use solana_sysvar::recent_blockhashes::{IterItem, RecentBlockhashes};
+
+        while blockhashes.len() > MAX_RECENT_BLOCKHASHES {
+            blockhashes.pop_front();
+        }
+        let sysvar: RecentBlockhashes = blockhashes
+            .iter()
+            .enumerate()
+            .map(|(i, blockhash)| IterItem(i as u64, blockhash, LAMPORTS_PER_SIGNATURE))
+            .collect();
+        self.svm.set_sysvar(&sysvar);
+        self.recent_blockhashes = blockhashes;
+        self.unsaved = true;
+    }
+
+    /// Create an independent copy of this fork's state and block production mode
+    fn duplicate(&self) -> Result<Arc<Mutex<Fork>>> {
+        let fork = Fork::new(self.svm.clone(), self.block_production)?;
+        {
+            let mut copy = fork.try_lock()?;
+            copy.pending_clock_ms = self.pending_clock_ms;
+            copy.recent_blockhashes = self.recent_blockhashes.clone();
+            copy.transactions = self.transactions.clone();
+            copy.origin = self.origin.clone();
+            copy.source_accounts = self.source_accounts.clone();
+            copy.lazy = self.lazy;
+            copy.missing_accounts = self.missing_accounts.clone();
+        }
+        Ok(fork)
+    }
+
+    /// Use the current state as the fork's origin
+    fn capture_origin(&mut self) {
+        self.origin = ForkSnapshot {
+            id: 0,
+            svm: self.svm.clone(),
+            pending_clock_ms: self.pending_clock_ms,
+            recent_blockhashes: self.recent_blockhashes.clone(),
+            transactions: self.transactions.clone(),
+        };
+        self.origin_unsaved = true;
+    }
+
+    /// Keys among `pubkeys` that lazy fetching should look up upstream
+    fn absent_accounts(&self, pubkeys: impl IntoIterator<Item = Pubkey>) -> Vec<Pubkey> {
+        let mut absent: Vec<Pubkey> = pubkeys
+            .into_iter()
+            .filter(|pubkey| {
+                !self.missing_accounts.contains(pubkey) && self.svm.get_account(pubkey).is_none()
+            })
+            .collect();
+        absent.sort();
+        absent.dedup();
+        absent
+    }
+
+    /// Add accounts fetched from upstream after creation. They become part of the origin too,
+    /// so diffs and resets treat them as if they had been fetched at creation. Accounts
+    /// already in the fork are left untouched.
+    fn add_upstream_accounts(&mut self, accounts: Vec<(Pubkey, Account)>) -> Result<()> {
+        for (pubkey, account) in accounts {
+            if self.svm.get_account(&pubkey).is_some() {
+                continue;
+            }
+            self.origin.svm.set_account(pubkey, account.clone())?;
+            self.set_account(pubkey, account)?;
+        }
+        self.origin_unsaved = true;
+        Ok(())
+    }
+
+    /// Whether an account differs from its state at creation
+    fn is_modified(&self, pubkey: &Pubkey) -> bool {
+        self.svm.get_account(pubkey) != self.origin.svm.get_account(pubkey)
+    }
+
+    /// Origin accounts that still hold their state at creation. Sysvars are not included.
+    fn untouched_accounts(&self) -> Vec<Pubkey> {
+        let mut untouched: Vec<Pubkey> = archive_accounts(&self.origin.svm)
+            .into_iter()
+            .filter(|(pubkey, account)| {
+                account.owner != solana_sdk::sysvar::ID
+                    && self.svm.get_account(pubkey).as_ref() == Some(account)
+            })
+            .map(|(pubkey, _)| pubkey)
+            .collect();
+        untouched.sort();
+        untouched
+    }
+
+    /// Replace accounts with their latest upstream state, `None` for accounts that no longer
+    /// exist upstream. The new state becomes part of the origin, so diffs and resets treat it
+    /// as if it had been fetched at creation.
+    fn refresh_upstream_accounts(
+        &mut self,
+        accounts: Vec<(Pubkey, Option<Account>)>,
+    ) -> Result<()> {
+        let mut accounts: Vec<(Pubkey, Account)> = accounts
+            .into_iter()
+            .map(|(pubkey, account)| (pubkey, account.unwrap_or_default()))
+            .collect();
+
+        // Removals sort first (non-executable); setting zero lamports removes the account
+        sort_for_loading(&mut accounts);
+        for (pubkey, account) in accounts {
+            if account.lamports == 0 {
+                if self.lazy {
+                    self.missing_accounts.insert(pubkey);
+                }
+            } else {
+                self.missing_accounts.remove(&pubkey);
+            }
+            self.origin.svm.set_account(pubkey, account.clone())?;
+            self.set_account(pubkey, account)?;
+        }
+        self.origin_unsaved = true;
+        Ok(())
+    }
+
+    /// Restore the state captured at creation. Snapshots stay valid.
+    pub fn reset(&mut self) {
+        self.svm = self.origin.svm.clone();
+        self.pending_clock_ms = self.origin.pending_clock_ms;
+        self.recent_blockhashes = self.origin.recent_blockhashes.clone();
+        self.transactions = self.origin.transactions.clone();
+        self.changed_accounts.clear();
+        self.unsaved = true;
+        self.replaced = true;
+    }
+
+    /// Replace the fork's state with freshly fetched state, which becomes the new origin
+    fn rebase(&mut self, svm: LiteSVM, source_blockhash: Option<Hash>) {
+        self.svm = svm.with_blockhash_check(false);
+        self.adopt_blockhash(source_blockhash.unwrap_or_else(|| self.svm.latest_blockhash()));
+        self.pending_clock_ms = 0;
+        self.transactions.clear();
+        self.capture_origin();
+        self.missing_accounts.clear();
+        self.changed_accounts.clear();
+        self.unsaved = true;
+        self.replaced = true;
+    }
+
+    /// Accounts created, deleted and modified since the fork was created
+    pub fn diff(&self) -> ForkDiff {
+        diff::diff_accounts(
+            &self.origin.svm.accounts_db().inner,
+            &self.svm.accounts_db().inner,
+        )
+    }
+
+    /// Apply a diff taken from another fork. All changes are validated before any is applied.
+    pub fn patch(&mut self, diff: &ForkDiff) -> Result<()> {
+        let mut accounts = Vec::new();
+        for created in &diff.created {
+            accounts.push((created.pubkey.parse()?, created.account.to_account()?));
+        }
+        for modified in &diff.modified {
+            let pubkey: Pubkey = modified.pubkey.parse()?;
+            let mut account = self
+                .svm
+                .get_account(&pubkey)
+                .ok_or_else(|| anyhow::anyhow!("Modified account {} not found in fork", pubkey))?;
+            diff::apply_modification(&mut account, modified)?;
+            accounts.push((pubkey, account));
+        }
+        for deleted in &diff.deleted {
+            accounts.push((deleted.parse()?, Account::default()));
+        }
+
+        // Deletions sort first (non-executable); setting zero lamports removes the account
+        sort_for_loading(&mut accounts);
+        for (pubkey, account) in accounts {
+            self.set_account(pubkey, account)?;
+        }
+        Ok(())
+    }
+
+    /// Capture the fork's complete state as a portable archive
+    fn to_archive(&self, info: &ForkInfo) -> ForkArchive {
+        ForkArchive {
+            manifest: ArchiveManifest {
+                transactions: self.transactions.clone(),
+                ..self.manifest(info)
+            },
+            accounts: archive_accounts(&self.svm),
+        }
+    }
+
+    fn origin_archive(&self, info: &ForkInfo) -> ForkArchive {
+        let origin = &self.origin;
+        ForkArchive {
+            manifest: ArchiveManifest {
+                pending_clock_ms: origin.pending_clock_ms,
+                transactions: origin.transactions.clone(),
+                ..self.manifest(info)
+            }
+            .with_recent_blockhashes(&origin.recent_blockhashes),
+            accounts: archive_accounts(&origin.svm),
+        }
+    }
+
+    /// Manifest of the current state, without the transaction history
+    fn manifest(&self, info: &ForkInfo) -> ArchiveManifest {
+        ArchiveManifest {
+            version: ARCHIVE_VERSION,
+            fork_id: info.fork_id.to_string(),
+            created_at: info.created_at,
+            exported_at: chrono::Utc::now(),
+            account_count: info.account_count,
+            blockhash: String::new(),
+            recent_blockhashes: Vec::new(),
+            pending_clock_ms: self.pending_clock_ms,
+            block_production: self.block_production,
+            transactions: Vec::new(),
+            source_slot: info.source_slot,
+            commitment: info.commitment,
+            cluster: info.cluster.clone(),
+            upstream_url: info.upstream_url.clone(),
+            idl: info.idl,
+            limits: info.limits.clone(),
+            source_accounts: self.source_accounts.clone(),
+            lazy: self.lazy,
+            missing_accounts: self
+                .missing_accounts
+                .iter()
+                .map(|pubkey| pubkey.to_string())
+                .collect(),
+        }
+        .with_recent_blockhashes(&self.recent_blockhashes)
+    }
+
+    /// Collect the changes made since the last save and mark the fork as saved.
+    /// Returns `None` if nothing changed.
+    fn take_pending_save(&mut self, info: &ForkInfo) -> Option<PendingSave> {
+        if !self.unsaved {
+            return None;
+        }
+
+        let changed = std::mem::take(&mut self.changed_accounts);
+        let replace = std::mem::take(&mut self.replaced);
+        let origin = std::mem::take(&mut self.origin_unsaved).then(|| self.origin_archive(info));
+        self.unsaved = false;
+
+        let transactions = if replace {
+            self.transactions.clone()
+        } else {
+            self.transactions[self.saved_transactions.min(self.transactions.len())..].to_vec()
+        };
+        self.saved_transactions = self.transactions.len();
+
+        if replace {
+            let archive = self.to_archive(info);
+            return Some(PendingSave {
+                manifest: ArchiveManifest {
+                    transactions: Vec::new(),
+                    ..archive.manifest
+                },
+                accounts: archive
+                    .accounts
+                    .into_iter()
+                    .map(|(pubkey, account)| (pubkey, Some(account)))
+                    .collect(),
+                transactions,
+                replace,
+                origin,
+            });
+        }
+
+        // Block production rewrites these sysvars without going through `set_account`,
+        // and they are small, so they are saved with every change
+        #[allow(deprecated)]
+        let sysvars = [
+            solana_sysvar::clock::ID,
+            solana_sysvar::recent_blockhashes::ID,
+        ];
+        let accounts = changed
+            .into_iter()
+            .chain(sysvars)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|pubkey| (pubkey, self.svm.get_account(&pubkey)))
+            .collect();
+
+        Some(PendingSave {
+            manifest: self.manifest(info),
+            accounts,
+            transactions,
+            replace,
+            origin,
+        })
+    }
+
+    /// Rebuild a fork from an archive without contacting upstream. The archived state
+    /// becomes the new fork's origin.
+    fn from_archive(archive: ForkArchive) -> Result<Arc<Mutex<Fork>>> {
+        let snapshot = ForkSnapshot::from_archive(archive.manifest.clone(), archive.accounts)?;
+
+        let fork = Fork::new(snapshot.svm, archive.manifest.block_production)?;
+        {
+            let mut imported = fork.try_lock()?;
+            imported.pending_clock_ms = snapshot.pending_clock_ms;
+            imported.set_recent_blockhashes(snapshot.recent_blockhashes);
+            imported.transactions = snapshot.transactions;
+            imported.source_accounts = archive.manifest.source_accounts;
+            imported.lazy = archive.manifest.lazy;
+            imported.missing_accounts = archive
+                .manifest
+                .missing_accounts
+                .iter()
+                .filter_map(|pubkey| pubkey.parse().ok())
+                .collect();
+            imported.capture_origin();
+        }
+        Ok(fork)
+    }
+
+    /// Save the complete liteSVM state (accounts, sysvars, blockhash, transaction history)
+    pub fn snapshot(&mut self) -> u64 {
+        let id = self.next_snapshot_id;
+        self.next_snapshot_id += 1;
+        self.snapshots.push(ForkSnapshot {
+            id,
+            svm: self.svm.clone(),
+            pending_clock_ms: self.pending_clock_ms,
+            recent_blockhashes: self.recent_blockhashes.clone(),
+            transactions: self.transactions.clone(),
+        });
+        id
+    }
+
+    /// Restore the state saved by snapshot `id`.
+    ///
+    /// Snapshots taken after `id` are invalidated. Snapshot `id` itself stays valid, so the
+    /// same snapshot can be reverted to repeatedly (e.g. between test cases).
+    pub fn revert(&mut self, id: u64) -> Result<()> {
+        let index = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Snapshot {} not found", id))?;
+
+        self.snapshots.truncate(index + 1);
+        let snapshot = &self.snapshots[index];
+        self.svm = snapshot.svm.clone();
+        self.pending_clock_ms = snapshot.pending_clock_ms;
+        self.recent_blockhashes = snapshot.recent_blockhashes.clone();
+        self.transactions = snapshot.transactions.clone();
+        self.changed_accounts.clear();
+        self.unsaved = true;
+        self.replaced = true;
+        Ok(())
+    }
+}
+
+impl ForkSnapshot {
+    fn from_archive(
+        manifest: ArchiveManifest,
+        mut accounts: Vec<(Pubkey, Account)>,
+    ) -> Result<Self> {
+        let mut svm = LiteSVM::new().with_blockhash_check(false);
+
+        // `LiteSVM::new()` funds an airdrop account with a random key, which is the only
+        // account two fresh instances don't share. Drop it so only archived accounts remain.
+        let baseline = LiteSVM::new();
+        let airdrop: Vec<Pubkey> = svm
+            .accounts_db()
+            .inner
+            .keys()
+            .filter(|pubkey| !baseline.accounts_db().inner.contains_key(*pubkey))
+            .copied()
+            .collect();
+        for pubkey in airdrop {
+            svm.set_account(pubkey, Account::default())?;
+        }
+
+        let recent_blockhashes = manifest.recent_blockhashes()?;
+
+        sort_for_loading(&mut accounts);
+        for (pubkey, account) in accounts {
+            svm.set_account(pubkey, account)?;
+        }
+
+        Ok(Self {
+            id: 0,
+            svm,
+            pending_clock_ms: manifest.pending_clock_ms,
+            recent_blockhashes,
+            transactions: manifest.transactions,
+        })
+    }
+}
+
+/// Accounts that differ from a fresh liteSVM instance. Builtins and default programs are
+/// recreated by `LiteSVM::new()` on import, so they are not stored.
+fn archive_accounts(svm: &LiteSVM) -> Vec<(Pubkey, Account)> {
+    let baseline = LiteSVM::new();
+    svm.accounts_db()
+        .inner
+        .iter()
+        .filter(|(pubkey, account)| baseline.accounts_db().inner.get(*pubkey) != Some(*account))
+        .map(|(pubkey, account)| (*pubkey, Account::from(account.clone())))
+        .collect()
+}
+
+/// Reject program account queries with an invalid program id or memcmp bytes before
+/// anything is fetched
+fn validate_program_accounts_query(query: &ProgramAccountsQuery) -> Result<()> {
+    query
+        .program_id
+        .parse::<Pubkey>()
+        .map_err(|e| anyhow::anyhow!("Invalid programId '{}': {}", query.program_id, e))?;
+    for filter in &query.filters {
+        if let ProgramAccountsFilter::Memcmp(memcmp) = filter {
+            memcmp
+                .decoded_bytes()
+                .map_err(|e| anyhow::anyhow!("Invalid memcmp bytes '{}': {}", memcmp.bytes, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Fail a strict fork creation if requested accounts are missing or accounts failed to decode
+fn check_strict(report: &FetchReport) -> Result<()> {
+    if !report.missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "strict: requested account(s) not found upstream: {}",
+            report.missing.join(", ")
+        ));
+    }
+    if !report.failed.is_empty() {
+        let failures: Vec<String> = report
+            .failed
+            .iter()
+            .map(|failure| format!("{} ({})", failure.pubkey, failure.error))
+            .collect();
+        return Err(anyhow::anyhow!(
+            "strict: account(s) failed to decode: {}",
+            failures.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Reject excluded accounts that are not valid pubkeys before anything is fetched
+fn validate_fetch_limits(limits: &FetchLimits) -> Result<()> {
+    for pubkey in &limits.exclude {
+        pubkey
+            .parse::<Pubkey>()
+            .map_err(|e| anyhow::anyhow!("Invalid exclude '{}': {}", pubkey, e))?;
+    }
+    Ok(())
+}
+
+/// Addresses a message loads through address lookup tables, split into writable and readonly.
+/// `get_table` looks up table accounts, e.g. in a fork. Tables it doesn't find or indexes past
+/// the end of a table are skipped; the transaction will fail to load in that case anyway.
+fn lookup_table_addresses(
+    get_table: impl Fn(&Pubkey) -> Option<Account>,
+    message: &VersionedMessage,
+) -> (Vec<Pubkey>, Vec<Pubkey>) {
+    let mut writable = Vec::new();
+    let mut readonly = Vec::new();
+    for lookup in message.address_table_lookups().unwrap_or_default() {
+        let Some(addresses) = get_table(&lookup.account_key)
+            .as_ref()
+            .and_then(dependencies::lookup_table_entries)
+        else {
+            continue;
+        };
+        let resolve = |indexes: &[u8]| {
+            indexes
+                .iter()
+                .filter_map(|i| addresses.get(*i as usize).copied())
+                .collect::<Vec<_>>()
+        };
+        writable.extend(resolve(&lookup.writable_indexes));
+        readonly.extend(resolve(&lookup.readonly_indexes));
+    }
+    (writable, readonly)
+}
+
+/// Save a fork's changes since the last save
+async fn save_fork_state(storage: &dyn Storage, info: &ForkInfo, fork: &mut Fork) -> Result<()> {
+    let Some(save) = fork.take_pending_save(info) else {
+        return Ok(());
+    };
+
+    debug!(
+        "Saving fork {} ({} accounts, {} transactions, replace: {})",
+        info.fork_id,
+        save.accounts.len(),
+        save.transactions.len(),
+        save.replace
+    );
+    if let Some(origin) = &save.origin {
+        storage.save_fork_origin(&info.fork_id, origin).await?;
+    }
+    storage
+        .save_fork_state(
+            &info.fork_id,
+            &save.manifest,
+            &save.accounts,
+            &save.transactions,
+            save.replace,
+        )
+        .await
+}
+
+/// Save a fork, logging failures and rewriting it in full on the next save instead
+async fn persist_fork(storage: &dyn Storage, fork_id: &ForkId, fork: &mut Fork) {
+    let info = match storage.get_fork(fork_id).await {
+        Ok(Some(info)) if !info.evicted => info,
+        Ok(_) => return,
+        Err(e) => {
+            error!("Failed to read fork {} metadata: {}", fork_id, e);
+            return;
+        }
+    };
+    if let Err(e) = save_fork_state(storage, &info, fork).await {
+        error!("Failed to save fork {}: {}", fork_id, e);
+        fork.unsaved = true;
+        fork.replaced = true;
+    }
+}
+
+/// Sort accounts to ensure correct order for liteSVM:
+/// 1. Non-executable accounts first
+/// 2. BPF program data accounts (non-executable, owned by BPF loader)
+/// 3. BPF programs (executable, owned by BPF loader)
+/// 4. Other executable accounts
+fn sort_for_loading(accounts: &mut [(Pubkey, Account)]) {
+    accounts.sort_by_key(|(_, account)| {
+        if dependencies::is_program_data(account) {
+            1 // Program data accounts (must come before programs)
+        } else if dependencies::is_program(account) {
+            2 // BPF programs (need program data to be set first)
+        } else if account.executable {
+            3 // Other executable accounts
+        } else {
+            0 // Non-executable accounts
+        }
+    });
+}
+
+impl Drop for Fork {
+    fn drop(&mut self) {
+        if let Some(producer) = self.producer.take() {
+            producer.abort();
+        }
+    }
+}
+
+/// Fork lifetime limits
+#[derive(Debug, Clone, Copy)]
+pub struct TtlConfig {
+    /// TTL of forks that don't request one
+    pub default_minutes: i64,
+    /// Longest TTL a fork may request
+    pub max_minutes: i64,
+}
+
+impl Default for TtlConfig {
+    fn default() -> Self {
+        Self {
+            default_minutes: DEFAULT_TTL_MINUTES,
+            max_minutes: 24 * 60,
+        }
+    }
+}
+
+impl TtlConfig {
+    /// Validate a requested TTL, falling back to the default
+    fn minutes(&self, requested: Option<i64>) -> Result<i64> {
+        match requested {
+            None => Ok(self.default_minutes),
+            Some(minutes) if (1..=self.max_minutes).contains(&minutes) => Ok(minutes),
+            Some(minutes) => Err(InvalidRequest(format!(
+                "ttlMinutes must be between 1 and {}, got {}",
+                self.max_minutes, minutes
+            ))
+            .into()),
+        }
+    }
+}
+
+/// Error for requests to a fork that outlived its TTL
+#[derive(Debug)]
+pub struct ForkExpired(pub ForkId);
+
+impl std::fmt::Display for ForkExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Fork {} expired", self.0)
+    }
+}
+
+impl std::error::Error for ForkExpired {}
+
+/// Error for requests the client has to fix, as opposed to failures while serving them
+#[derive(Debug)]
+pub struct InvalidRequest(pub String);
+
+impl std::fmt::Display for InvalidRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRequest {}
+
+fn invalid_request(e: anyhow::Error) -> anyhow::Error {
+    InvalidRequest(e.to_string()).into()
+}
+
+/// Manages all active forks. Forks are held in memory and, with a durable storage backend,
+/// saved after every change and reloaded on first access after a restart.
 pub struct ForkManager {
-    storage: Storage,
-    forks: Arc<RwLock<HashMap<ForkId, Arc<Mutex<LiteSVM>>>>>,
+    storage: Arc<dyn Storage>,
+    forks: Arc<RwLock<HashMap<ForkId, Arc<Mutex<Fork>>>>>,
+    events: broadcast::Sender<ForkEvent>,
+    ttl: TtlConfig,
     base_url: String,
-    solana_rpc: String,
+    upstreams: Upstreams,
+    fetch_concurrency: usize,
+    /// Accounts fetched from upstream, shared by all forks
+    cache: AccountCache,
 }
 
 impl ForkManager {
-    pub fn new(storage: Storage, base_url: String, solana_rpc: String) -> Self {
+    pub fn new(storage: Arc<dyn Storage>, base_url: String, upstreams: Upstreams) -> Self {
         Self {
             storage,
             forks: Arc::new(RwLock::new(HashMap::new())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            ttl: TtlConfig::default(),
             base_url,
-            solana_rpc,
+            upstreams,
+            fetch_concurrency: DEFAULT_FETCH_CONCURRENCY,
+            cache: AccountCache::default(),
+        }
+    }
+
+    /// Replace the default upstream account cache
+    pub fn with_cache(mut self, cache: AccountCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// Replace the default fork lifetime limits
+    pub fn with_ttl(mut self, ttl: TtlConfig) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Receive fork lifecycle events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<ForkEvent> {
+        self.events.subscribe()
+    }
+
+    /// Evict expired forks every `interval`
+    pub fn spawn_reaper(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                if let Err(e) = manager.reap_expired_forks().await {
+                    error!("Failed to reap expired forks: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Evict every fork past its expiry and return their ids. Forks evicted more than
+    /// `EXPIRED_RETENTION` ago are deleted entirely.
+    pub async fn reap_expired_forks(&self) -> Result<Vec<ForkId>> {
+        let mut reaped = Vec::new();
+        for info in self.storage.list_forks().await? {
+            if info.evicted {
+                if info.expires_at + EXPIRED_RETENTION < chrono::Utc::now() {
+                    self.storage.delete_fork(&info.fork_id).await?;
+                }
+                continue;
+            }
+            if !info.is_expired() {
+                continue;
+            }
+            // A request may have refreshed the TTL or evicted the fork since they were listed
+            if self.evict_fork(&info.fork_id).await? {
+                reaped.push(info.fork_id);
+            }
+        }
+        Ok(reaped)
+    }
+
+    /// Drop an expired fork from memory and its state from storage, keeping its metadata
+    /// marked as evicted, and publish a `ForkEvent::Expired`. Returns whether this call evicted
+    /// the fork, rather than the reaper or a request that got to it first.
+    async fn evict_fork(&self, fork_id: &ForkId) -> Result<bool> {
+        // Holding the fork map serializes evictions, so each fork is evicted once
+        let mut forks = self.forks.write().await;
+        let mut info = match self.storage.get_fork(fork_id).await? {
+            Some(info) if info.is_expired() && !info.evicted => info,
+            _ => return Ok(false),
+        };
+        info.evicted = true;
+        self.storage.save_fork(&info).await?;
+        forks.remove(fork_id);
+        self.storage.delete_fork_state(fork_id).await?;
+        info!(
+            "Fork {} expired at {} and was evicted",
+            fork_id, info.expires_at
+        );
+        // Nobody may be listening
+        let _ = self.events.send(ForkEvent::Expired {
+            fork_id: fork_id.clone(),
+            expired_at: info.expires_at,
+        });
+        Ok(true)
+    }
+
+    /// Fail with `ForkExpired` if the fork was evicted or is past its expiry, evicting it now
+    /// rather than letting the request refresh its TTL
+    pub async fn ensure_not_expired(&self, fork_id: &ForkId) -> Result<()> {
+        match self.storage.get_fork(fork_id).await? {
+            Some(info) if info.is_expired() => {
+                if !info.evicted {
+                    self.evict_fork(fork_id).await?;
+                }
+                Err(ForkExpired(fork_id.clone()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Set how many account batches are fetched from upstream concurrently (at least 1)
+    pub fn with_fetch_concurrency(mut self, fetch_concurrency: usize) -> Self {
+        self.fetch_concurrency = fetch_concurrency.max(1);
+        self
+    }
+
+    /// Upstream a fork fetches accounts from: its ad-hoc endpoint or its cluster
+    fn upstream(&self, info: &ForkInfo) -> Result<UpstreamClient> {
+        match (&info.upstream_url, &info.cluster) {
+            (Some(url), _) => self.upstreams.url(url),
+            (None, Some(cluster)) => self.upstreams.cluster(cluster),
+            (None, None) => self.upstreams.cluster(self.upstreams.default_cluster()),
+        }
+    }
+
+    /// Resolve a fork creation request: its upstream, fetch options and the transaction it is
+    /// created from, whose accounts are added to the requested ones
+    async fn resolve_source(&self, req: &CreateForkRequest) -> Result<ForkSource> {
+        let (cluster, upstream_url) = match (&req.cluster, &req.rpc_url) {
+            (Some(_), Some(_)) => {
+                return Err(
+                    InvalidRequest("cluster and rpcUrl are mutually exclusive".to_string()).into(),
+                )
+            }
+            (None, Some(url)) => (None, Some(url.clone())),
+            (cluster, None) => (
+                Some(
+                    cluster
+                        .clone()
+                        .unwrap_or_else(|| self.upstreams.default_cluster().to_string()),
+                ),
+                None,
+            ),
+        };
+        let upstream = match (&upstream_url, &cluster) {
+            (Some(url), _) => self.upstreams.url(url),
+            (None, cluster) => self
+                .upstreams
+                .cluster(cluster.as_deref().unwrap_or_default()),
+        }
+        .map_err(|e| match e.is::<RpcUrlNotAllowed>() {
+            true => e,
+            false => invalid_request(e),
+        })?;
+
+        for pubkey in &req.accounts {
+            pubkey
+                .parse::<Pubkey>()
+                .map_err(|e| InvalidRequest(format!("Invalid pubkey '{}': {}", pubkey, e)))?;
         }
+        for query in &req.program_accounts {
+            validate_program_accounts_query(query).map_err(invalid_request)?;
+        }
+        validate_fetch_limits(&req.limits).map_err(invalid_request)?;
+
+        let options = FetchOptions {
+            commitment: req.commitment,
+            min_context_slot: req.min_context_slot,
+            fresh: req.fresh,
+            idl: req.idl,
+        };
+
+        // Accounts of the transaction the fork is created from
+        let transaction = match (&req.transaction, &req.signature) {
+            (Some(_), Some(_)) => {
+                return Err(InvalidRequest(
+                    "transaction and signature are mutually exclusive".to_string(),
+                )
+                .into())
+            }
+            (Some(tx_data), None) => {
+                let tx_bytes = base64::engine::general_purpose::STANDARD
+                    .decode(tx_data)
+                    .map_err(|e| InvalidRequest(format!("Invalid transaction: {}", e)))?;
+                Some(
+                    bincode::deserialize::<VersionedTransaction>(&tx_bytes)
+                        .map_err(|e| InvalidRequest(format!("Invalid transaction: {}", e)))?,
+                )
+            }
+            (None, Some(signature)) => {
+                Some(Self::get_transaction(&upstream, signature, &options).await?)
+            }
+            (None, None) if req.execute => {
+                return Err(InvalidRequest(
+                    "execute requires a transaction or signature".to_string(),
+                )
+                .into())
+            }
+            (None, None) => None,
+        };
+        let mut account_pubkeys = req.accounts.clone();
+        if let Some(transaction) = &transaction {
+            for pubkey in
+                Self::transaction_account_keys(&upstream, &transaction.message, &options).await?
+            {
+                if !account_pubkeys.contains(&pubkey) {
+                    account_pubkeys.push(pubkey);
+                }
+            }
+        }
+
+        Ok(ForkSource {
+            cluster,
+            upstream_url,
+            upstream,
+            options,
+            account_pubkeys,
+            transaction,
+        })
+    }
+
+    /// Resolve the accounts a fork creation request would load, without creating the fork.
+    /// Returns them in loading order with what couldn't be fetched and the upstream slot they
+    /// were fetched at.
+    pub async fn plan_fork(
+        &self,
+        req: CreateForkRequest,
+    ) -> Result<(Vec<PlannedAccount>, FetchReport, Option<u64>)> {
+        let source = self.resolve_source(&req).await?;
+        info!(
+            "Planning fork with {} accounts",
+            source.account_pubkeys.len()
+        );
+        let fetched = self
+            .fetch_mainnet_accounts(
+                &source.upstream,
+                &source.account_pubkeys,
+                &req.program_accounts,
+                &req.limits,
+                source.options,
+            )
+            .await?;
+
+        let accounts = fetched
+            .accounts
+            .iter()
+            .map(|(pubkey, account)| {
+                let pubkey_str = pubkey.to_string();
+                let inclusion = fetched.inclusions.get(&pubkey_str);
+                let mut reason = inclusion.map_or(InclusionReason::Requested, |i| i.reason);
+                if reason == InclusionReason::Requested && !req.accounts.contains(&pubkey_str) {
+                    reason = InclusionReason::Transaction;
+                }
+                PlannedAccount {
+                    pubkey: pubkey_str,
+                    owner: account.owner.to_string(),
+                    executable: account.executable,
+                    size: account.data.len(),
+                    reason,
+                    dependency_of: inclusion
+                        .and_then(|i| i.dependency_of)
+                        .map(|pubkey| pubkey.to_string()),
+                    depth: inclusion.map_or(0, |i| i.depth),
+                }
+            })
+            .collect();
+        Ok((accounts, fetched.report(), fetched.slot))
     }
 
-    /// Create a new fork
-    pub async fn create_fork(&self, account_pubkeys: Vec<String>) -> Result<ForkInfo> {
+    /// Create a new fork. Returns its info, what was fetched for it and, if `execute` was
+    /// requested, the result of the transaction it was created from.
+    pub async fn create_fork(
+        &self,
+        req: CreateForkRequest,
+    ) -> Result<(ForkInfo, FetchReport, Option<TransactionRecord>)> {
         let fork_id = ForkId::new();
         info!(
             "Creating fork {} with {} accounts",
             fork_id,
-            account_pubkeys.len()
+            req.accounts.len()
+        );
+
+        let ForkSource {
+            cluster,
+            upstream_url,
+            upstream,
+            options,
+            account_pubkeys,
+            transaction,
+        } = self.resolve_source(&req).await?;
+        let mut fork_info = ForkInfo::new(
+            fork_id.clone(),
+            &self.base_url,
+            account_pubkeys.len(),
+            self.ttl.minutes(req.ttl_minutes)?,
         );
+        fork_info.pinned = req.pinned;
+        fork_info.refresh_on_touch = req.refresh_on_touch;
+        fork_info.cluster = cluster;
+        fork_info.upstream_url = upstream_url;
+
+        let fetched = self
+            .fetch_mainnet_accounts(
+                &upstream,
+                &account_pubkeys,
+                &req.program_accounts,
+                &req.limits,
+                options,
+            )
+            .await?;
+
+        let report = fetched.report();
+        if !report.missing.is_empty() || !report.failed.is_empty() {
+            warn!(
+                "Fork {}: {} requested account(s) not found upstream, {} account(s) failed to decode",
+                fork_id,
+                report.missing.len(),
+                report.failed.len()
+            );
+        }
+        if req.strict {
+            check_strict(&report)?;
+        }
+
+        // Matched accounts become source accounts, so a refetch reloads the same set
+        let mut source_accounts = account_pubkeys;
+        for pubkey in &fetched.matched {
+            if !source_accounts.contains(pubkey) {
+                source_accounts.push(pubkey.clone());
+            }
+        }
+        if !fetched.matched.is_empty() {
+            info!(
+                "Program account queries matched {} account(s)",
+                fetched.matched.len()
+            );
+        }
+
+        let (svm, context) = self.build_svm(&upstream, fetched, options).await?;
+        let fork = Fork::new(svm, req.block_production)?;
+        fork_info.account_count = source_accounts.len();
+        let mut executed = None;
+        {
+            let mut fork = fork.lock().await;
+            fork.source_accounts = source_accounts;
+            fork.lazy = req.lazy;
+            if let Some(blockhash) = context.blockhash {
+                fork.adopt_blockhash(blockhash);
+                fork.capture_origin();
+            }
+            if let Some(transaction) = transaction.filter(|_| req.execute) {
+                executed = Some(Self::replay_transaction(&mut fork, transaction)?);
+            }
+        }
+
+        // Save metadata and state to storage
+        fork_info.source_slot = context.slot;
+        fork_info.chain_sync = context.sync;
+        fork_info.commitment = req.commitment;
+        fork_info.idl = req.idl;
+        fork_info.limits = req.limits;
+        self.storage.save_fork(&fork_info).await?;
+        self.save_fork_state(&fork_info, &mut *fork.lock().await)
+            .await?;
+
+        // Store in memory
+        let mut forks = self.forks.write().await;
+        forks.insert(fork_id, fork);
+
+        info!("Fork {} created successfully", fork_info.fork_id);
+        Ok((fork_info, report, executed))
+    }
+
+    /// Fetch a confirmed transaction from upstream by signature
+    async fn get_transaction(
+        upstream: &UpstreamClient,
+        signature: &str,
+        options: &FetchOptions,
+    ) -> Result<VersionedTransaction> {
+        // getTransaction doesn't serve processed transactions
+        let commitment = match options.commitment {
+            Commitment::Processed => Commitment::Confirmed,
+            commitment => commitment,
+        };
+        let result = upstream
+            .call(
+                "getTransaction",
+                json!([signature, {
+                    "encoding": "base64",
+                    "commitment": commitment.as_str(),
+                    "maxSupportedTransactionVersion": 0
+                }]),
+            )
+            .await?;
+        if result.is_null() {
+            return Err(anyhow::anyhow!(
+                "Transaction {} not found upstream",
+                signature
+            ));
+        }
+        let tx_data = result["transaction"][0].as_str().ok_or_else(|| {
+            anyhow::anyhow!("Unexpected getTransaction response: missing transaction")
+        })?;
+        let tx_bytes = base64::engine::general_purpose::STANDARD.decode(tx_data)?;
+        Ok(bincode::deserialize(&tx_bytes)?)
+    }
+
+    /// Static keys of a message, its lookup tables and the addresses it loads through them.
+    /// Tables are fetched from upstream to resolve the addresses.
+    async fn transaction_account_keys(
+        upstream: &UpstreamClient,
+        message: &VersionedMessage,
+        options: &FetchOptions,
+    ) -> Result<Vec<String>> {
+        let mut keys: Vec<Pubkey> = message.static_account_keys().to_vec();
+        let tables: Vec<String> = message
+            .address_table_lookups()
+            .unwrap_or_default()
+            .iter()
+            .map(|lookup| lookup.account_key.to_string())
+            .collect();
+
+        if !tables.is_empty() {
+            let mut table_accounts = HashMap::new();
+            for chunk in tables.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+                let (_, values) = Self::get_multiple_accounts(upstream, chunk, options).await?;
+                for (table, value) in chunk.iter().zip(values) {
+                    if value.is_null() {
+                        return Err(anyhow::anyhow!(
+                            "Address lookup table {} not found upstream",
+                            table
+                        ));
+                    }
+                    let table: Pubkey = table.parse()?;
+                    table_accounts.insert(table, Self::parse_account(&table, &value)?);
+                }
+            }
+            let (writable, readonly) =
+                lookup_table_addresses(|key| table_accounts.get(key).cloned(), message);
+            keys.extend(table_accounts.into_keys());
+            keys.extend(writable);
+            keys.extend(readonly);
+        }
 
-        // Fetch accounts from mainnet
-        let accounts = self.fetch_mainnet_accounts(&account_pubkeys).await?;
+        let mut seen = HashSet::new();
+        Ok(keys
+            .into_iter()
+            .filter(|key| seen.insert(*key))
+            .map(|key| key.to_string())
+            .collect())
+    }
+
+    /// Fetch accounts and everything they depend on (owners, program data) from a cluster,
+    /// or the default cluster. Returns them in loading order with the slot they reflect.
+    pub async fn fetch_accounts(
+        &self,
+        cluster: Option<&str>,
+        pubkeys: &[String],
+    ) -> Result<(Vec<(Pubkey, Account)>, Option<u64>)> {
+        let upstream = self
+            .upstreams
+            .cluster(cluster.unwrap_or(self.upstreams.default_cluster()))?;
+        let FetchedAccounts { accounts, slot, .. } = self
+            .fetch_mainnet_accounts(
+                &upstream,
+                pubkeys,
+                &[],
+                &FetchLimits::default(),
+                FetchOptions::default(),
+            )
+            .await?;
+        Ok((accounts, slot))
+    }
+
+    /// Upstream block time of `slot` on a cluster, or the default cluster
+    pub async fn block_time(&self, cluster: Option<&str>, slot: u64) -> Result<i64> {
+        let upstream = self
+            .upstreams
+            .cluster(cluster.unwrap_or(self.upstreams.default_cluster()))?;
+        Self::get_block_time(&upstream, slot).await
+    }
+
+    /// Load accounts fetched from mainnet into a new liteSVM instance.
+    /// Returns the instance and the upstream chain context it was synced to.
+    async fn build_svm(
+        &self,
+        upstream: &UpstreamClient,
+        fetched: FetchedAccounts,
+        options: FetchOptions,
+    ) -> Result<(LiteSVM, ChainContext)> {
+        let FetchedAccounts { accounts, slot, .. } = fetched;
 
         // Create new liteSVM instance
         let mut svm = LiteSVM::new();
@@ -52,24 +1577,205 @@ impl ForkManager {
             svm.set_account(pubkey, account)?;
         }
 
-        // Initialize chain context (slot, blockhash best-effort)
-        self.initialize_chain_context(&mut svm).await.ok();
+        let context = self
+            .initialize_chain_context(upstream, &mut svm, slot, options)
+            .await;
+        if !context.sync.synced {
+            warn!(
+                "Chain context partially synced from upstream: {}",
+                context.sync.errors.join("; ")
+            );
+        }
+
+        Ok((svm, context))
+    }
+
+    /// Restore a fork to its creation state, keeping its id and RPC URL. With `refetch` the
+    /// accounts requested at creation are pulled from upstream again and become the new
+    /// creation state. Returns the fork's info and slot after the reset, or `None` if the
+    /// fork does not exist.
+    pub async fn reset_fork(
+        &self,
+        fork_id: &ForkId,
+        refetch: bool,
+    ) -> Result<Option<(ForkInfo, u64)>> {
+        let Some(mut info) = self.storage.get_fork(fork_id).await? else {
+            return Ok(None);
+        };
+        let Some(fork) = self.load_fork(fork_id).await? else {
+            return Ok(None);
+        };
+
+        // Fetch without holding the fork lock so the fork keeps serving requests meanwhile
+        let svm = if refetch {
+            let account_pubkeys = fork.lock().await.source_accounts.clone();
+            info!(
+                "Refetching {} accounts for fork {}",
+                account_pubkeys.len(),
+                fork_id
+            );
+            // Never go back to accounts older than the current creation state
+            let options = FetchOptions {
+                commitment: info.commitment,
+                min_context_slot: info.source_slot,
+                fresh: false,
+                idl: info.idl,
+            };
+            let upstream = self.upstream(&info)?;
+            let fetched = self
+                .fetch_mainnet_accounts(&upstream, &account_pubkeys, &[], &info.limits, options)
+                .await?;
+            let (svm, context) = self.build_svm(&upstream, fetched, options).await?;
+            info.source_slot = context.slot;
+            info.chain_sync = context.sync;
+            self.storage.save_fork(&info).await?;
+            Some((svm, context.blockhash))
+        } else {
+            None
+        };
+
+        let mut fork = fork.lock().await;
+        match svm {
+            Some((svm, blockhash)) => fork.rebase(svm, blockhash),
+            None => fork.reset(),
+        }
+        self.persist_fork(fork_id, &mut fork).await;
+
+        let slot = fork.svm.get_sysvar::<Clock>().slot;
+        info!("Fork {} reset to slot {}", fork_id, slot);
+        Ok(Some((info, slot)))
+    }
+
+    /// Clone an existing fork into a new fork with its own id and TTL.
+    /// Returns `None` if the source fork does not exist.
+    pub async fn clone_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
+        let Some(source_info) = self.storage.get_fork(fork_id).await? else {
+            return Ok(None);
+        };
+        let Some(source) = self.load_fork(fork_id).await? else {
+            return Ok(None);
+        };
+
+        let new_fork_id = ForkId::new();
+        let fork = source.lock().await.duplicate()?;
+
+        let mut fork_info = ForkInfo::new(
+            new_fork_id.clone(),
+            &self.base_url,
+            source_info.account_count,
+            source_info.ttl_minutes,
+        );
+        fork_info.pinned = source_info.pinned;
+        fork_info.refresh_on_touch = source_info.refresh_on_touch;
+        fork_info.source_slot = source_info.source_slot;
+        fork_info.commitment = source_info.commitment;
+        fork_info.idl = source_info.idl;
+        fork_info.limits = source_info.limits.clone();
+        fork_info.chain_sync = source_info.chain_sync.clone();
+        fork_info.cluster = source_info.cluster.clone();
+        fork_info.upstream_url = source_info.upstream_url.clone();
+        self.storage.save_fork(&fork_info).await?;
+        self.save_fork_state(&fork_info, &mut *fork.lock().await)
+            .await?;
 
-        // Store in memory
         let mut forks = self.forks.write().await;
-        forks.insert(fork_id.clone(), Arc::new(Mutex::new(svm)));
+        forks.insert(new_fork_id, fork);
+
+        info!("Fork {} cloned into {}", fork_id, fork_info.fork_id);
+        Ok(Some(fork_info))
+    }
+
+    /// Export a fork's complete state. Returns `None` if the fork does not exist.
+    pub async fn export_fork(&self, fork_id: &ForkId) -> Result<Option<ForkArchive>> {
+        let Some(info) = self.storage.get_fork(fork_id).await? else {
+            return Ok(None);
+        };
+        let Some(fork) = self.load_fork(fork_id).await? else {
+            return Ok(None);
+        };
+
+        let archive = fork.lock().await.to_archive(&info);
+        info!(
+            "Exported fork {} with {} accounts",
+            fork_id,
+            archive.accounts.len()
+        );
+        Ok(Some(archive))
+    }
 
-        // Save metadata to in-memory storage
-        let account_count = account_pubkeys.len();
-        let fork_info = ForkInfo::new(fork_id, &self.base_url, account_count);
+    /// Recreate a fork from an exported archive under a new fork id
+    pub async fn import_fork(&self, archive: ForkArchive) -> Result<ForkInfo> {
+        let fork_id = ForkId::new();
+        let manifest = &archive.manifest;
+        let source_fork_id = manifest.fork_id.clone();
+        let mut fork_info = ForkInfo::new(
+            fork_id.clone(),
+            &self.base_url,
+            manifest.account_count,
+            self.ttl.default_minutes,
+        );
+        fork_info.source_slot = manifest.source_slot;
+        fork_info.commitment = manifest.commitment;
+        fork_info.cluster = manifest.cluster.clone();
+        fork_info.upstream_url = manifest.upstream_url.clone();
+        fork_info.idl = manifest.idl;
+        fork_info.limits = manifest.limits.clone();
+
+        // The fork fetches from the same upstream as the exported one, which this server
+        // must know and allow
+        self.upstream(&fork_info)
+            .map_err(|e| match e.is::<RpcUrlNotAllowed>() {
+                true => e,
+                false => invalid_request(e),
+            })?;
+        validate_fetch_limits(&fork_info.limits).map_err(invalid_request)?;
+
+        let fork = Fork::from_archive(archive)?;
         self.storage.save_fork(&fork_info).await?;
+        self.save_fork_state(&fork_info, &mut *fork.lock().await)
+            .await?;
 
-        info!("Fork {} created successfully", fork_info.fork_id);
+        let mut forks = self.forks.write().await;
+        forks.insert(fork_id, fork);
+
+        info!(
+            "Imported fork {} from archive of fork {}",
+            fork_info.fork_id, source_fork_id
+        );
         Ok(fork_info)
     }
 
+    /// Change a fork's TTL or pin, restarting its lifetime. Returns `None` if the fork does not
+    /// exist.
+    pub async fn extend_fork(
+        &self,
+        fork_id: &ForkId,
+        req: &ExtendForkRequest,
+    ) -> Result<Option<ForkInfo>> {
+        self.ensure_not_expired(fork_id).await?;
+        let Some(mut info) = self.storage.get_fork(fork_id).await? else {
+            return Ok(None);
+        };
+
+        if let Some(ttl_minutes) = req.ttl_minutes {
+            info.ttl_minutes = self.ttl.minutes(Some(ttl_minutes))?;
+        }
+        if let Some(pinned) = req.pinned {
+            info.pinned = pinned;
+        }
+        info.extend();
+        self.storage.save_fork(&info).await?;
+
+        info!(
+            "Fork {} extended to {} (TTL {} minutes, pinned: {})",
+            fork_id, info.expires_at, info.ttl_minutes, info.pinned
+        );
+        Ok(Some(info))
+    }
+
     /// Refresh fork TTL and return updated info
     pub async fn touch_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
+        self.ensure_not_expired(fork_id).await?;
         self.storage.refresh_fork(fork_id).await
     }
 
@@ -78,13 +1784,6 @@ impl ForkManager {
         self.storage.get_fork(fork_id).await
     }
 
-    /// Increment slot by 1
-    pub fn increment_slot(svm: &mut LiteSVM) {
-        let mut clock = svm.get_sysvar::<Clock>();
-        clock.slot += 1;
-        svm.set_sysvar::<Clock>(&clock);
-    }
-
     /// Delete a fork
     pub async fn delete_fork(&self, fork_id: &ForkId) -> Result<()> {
         let mut forks = self.forks.write().await;
@@ -96,29 +1795,49 @@ impl ForkManager {
 
     /// Handle RPC request for a specific fork
     pub async fn handle_rpc(&self, fork_id: &ForkId, req: JsonRpcRequest) -> JsonRpcResponse {
+        let not_found = |id| JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code: -32602,
+                message: "Fork not found or expired".to_string(),
+            }),
+        };
+
+        if let Err(e) = self.ensure_not_expired(fork_id).await {
+            return Self::into_response(req.id, Err(e));
+        }
+
         // Refresh TTL on any interaction
-        let _ = self.storage.refresh_fork(fork_id).await;
-
-        let forks = self.forks.read().await;
-        let svm = match forks.get(fork_id) {
-            Some(svm) => svm.clone(),
-            None => {
-                return JsonRpcResponse {
-                    jsonrpc: "2.0".to_string(),
-                    id: req.id,
-                    result: None,
-                    error: Some(JsonRpcError {
-                        code: -32602,
-                        message: "Fork not found or expired".to_string(),
-                    }),
-                };
+        let info = match self.storage.refresh_fork(fork_id).await {
+            Ok(Some(info)) => info,
+            Ok(None) => return not_found(req.id),
+            Err(e) => {
+                error!("Failed to refresh fork {}: {}", fork_id, e);
+                return Self::into_response(
+                    req.id,
+                    Err(anyhow::anyhow!("Failed to load fork: {}", e)),
+                );
             }
         };
 
-        drop(forks); // Release read lock
+        let fork = match self.load_fork(fork_id).await {
+            Ok(Some(fork)) => fork,
+            Ok(None) => return not_found(req.id),
+            Err(e) => {
+                error!("Failed to load fork {}: {}", fork_id, e);
+                return Self::into_response(
+                    req.id,
+                    Err(anyhow::anyhow!("Failed to load fork: {}", e)),
+                );
+            }
+        };
 
-        let mut svm = svm.lock().await;
-        self.process_rpc_method(&mut svm, req).await
+        let mut fork = fork.lock().await;
+        let response = self.process_rpc_method(&mut fork, &info, req).await;
+        self.persist_fork(fork_id, &mut fork).await;
+        response
     }
 
     /// Set account data on a fork
@@ -128,60 +1847,440 @@ impl ForkManager {
         pubkey: &Pubkey,
         account: Account,
     ) -> Result<()> {
-        let forks = self.forks.read().await;
-        let svm = forks
-            .get(fork_id)
+        let fork = self
+            .load_fork(fork_id)
+            .await?
             .ok_or_else(|| anyhow::anyhow!("Fork not found"))?;
-        let mut svm = svm.lock().await;
-        svm.set_account(*pubkey, account)?;
+        let mut fork = fork.lock().await;
+        fork.set_account(*pubkey, account)?;
+        self.persist_fork(fork_id, &mut fork).await;
         Ok(())
     }
-    /// Fetch accounts from mainnet recursively, getting all accounts in reverse order of ownership
-    /// Returns Vec to preserve insertion order (program data before programs)
-    async fn fetch_mainnet_accounts(&self, pubkeys: &[String]) -> Result<Vec<(Pubkey, Account)>> {
-        if pubkeys.is_empty() {
-            return Ok(Vec::new());
+
+    /// Diff a fork against its creation state. Returns `None` if the fork does not exist.
+    pub async fn diff_fork(&self, fork_id: &ForkId) -> Result<Option<ForkDiff>> {
+        let Some(fork) = self.load_fork(fork_id).await? else {
+            return Ok(None);
+        };
+        let diff = fork.lock().await.diff();
+        Ok(Some(diff))
+    }
+
+    /// Apply a diff to a fork. Returns `None` if the fork does not exist.
+    pub async fn patch_fork(&self, fork_id: &ForkId, diff: &ForkDiff) -> Result<Option<()>> {
+        let Some(fork) = self.load_fork(fork_id).await? else {
+            return Ok(None);
+        };
+        let mut fork = fork.lock().await;
+        fork.patch(diff)?;
+        self.persist_fork(fork_id, &mut fork).await;
+
+        info!(
+            "Patched fork {}: {} created, {} deleted, {} modified",
+            fork_id,
+            diff.created.len(),
+            diff.deleted.len(),
+            diff.modified.len()
+        );
+        Ok(Some(()))
+    }
+
+    /// Fetch accounts absent from a lazy fork from upstream, with their owners and programdata.
+    /// Keys upstream doesn't know are remembered and not requested again.
+    async fn fetch_absent_accounts(
+        &self,
+        fork: &mut Fork,
+        info: &ForkInfo,
+        pubkeys: impl IntoIterator<Item = Pubkey>,
+    ) -> Result<()> {
+        let absent = fork.absent_accounts(pubkeys);
+        if absent.is_empty() {
+            return Ok(());
         }
 
-        let mut all_accounts = Vec::new();
-        let mut processed_pubkeys = std::collections::HashSet::new();
+        let requested: Vec<String> = absent.iter().map(|pubkey| pubkey.to_string()).collect();
+        let fetched = self
+            .fetch_mainnet_accounts(
+                &self.upstream(info)?,
+                &requested,
+                &[],
+                &FetchLimits::default(),
+                FetchOptions::for_fork(info),
+            )
+            .await?;
+        // Accounts that failed to decode exist upstream, so they must not be remembered as missing
+        fetched.ensure_decoded()?;
+        let accounts = fetched.accounts;
+
+        let found: HashSet<Pubkey> = accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+        let missing: Vec<Pubkey> = absent
+            .into_iter()
+            .filter(|pubkey| !found.contains(pubkey))
+            .collect();
+        info!(
+            "Lazily fetched {} account(s), {} not found upstream",
+            accounts.len(),
+            missing.len()
+        );
+
+        fork.add_upstream_accounts(accounts)?;
+        fork.missing_accounts.extend(missing);
+        fork.unsaved = true;
+        Ok(())
+    }
 
-        self.fetch_accounts_recursive(pubkeys, &mut all_accounts, &mut processed_pubkeys)
+    /// Fetch the accounts a transaction references that a lazy fork doesn't have yet, including
+    /// address lookup tables and the addresses they resolve to
+    async fn fetch_transaction_accounts(
+        &self,
+        fork: &mut Fork,
+        info: &ForkInfo,
+        message: &VersionedMessage,
+    ) -> Result<()> {
+        let tables = message
+            .address_table_lookups()
+            .unwrap_or_default()
+            .iter()
+            .map(|lookup| lookup.account_key);
+        let static_keys = message.static_account_keys().iter().copied();
+        self.fetch_absent_accounts(fork, info, static_keys.chain(tables))
             .await?;
 
-        // Sort accounts to ensure correct order for liteSVM:
-        // 1. Non-executable accounts first
-        // 2. BPF program data accounts (non-executable, owned by BPF loader)
-        // 3. BPF programs (executable, owned by BPF loader)
-        // 4. Other executable accounts
-        let bpf_loader = "BPFLoaderUpgradeab1e11111111111111111111111"
-            .parse::<Pubkey>()
-            .unwrap();
+        let (writable, readonly) = lookup_table_addresses(|key| fork.svm.get_account(key), message);
+        self.fetch_absent_accounts(fork, info, writable.into_iter().chain(readonly))
+            .await
+    }
+
+    /// Get a loaded fork, rehydrating it from storage if it has not been loaded since a restart
+    async fn load_fork(&self, fork_id: &ForkId) -> Result<Option<Arc<Mutex<Fork>>>> {
+        self.ensure_not_expired(fork_id).await?;
+        if let Some(fork) = self.forks.read().await.get(fork_id) {
+            return Ok(Some(fork.clone()));
+        }
+        if !self.storage.persists_state() || self.storage.get_fork(fork_id).await?.is_none() {
+            return Ok(None);
+        }
+
+        // Hold the write lock while loading so concurrent requests rehydrate only once
+        let mut forks = self.forks.write().await;
+        if let Some(fork) = forks.get(fork_id) {
+            return Ok(Some(fork.clone()));
+        }
+        let Some(archive) = self.storage.load_fork_state(fork_id).await? else {
+            return Ok(None);
+        };
+
+        let origin = self.storage.load_fork_origin(fork_id).await?;
+
+        let account_count = archive.accounts.len();
+        let fork = Fork::from_archive(archive)?;
+        {
+            let mut loaded = fork.try_lock()?;
+            if let Some(origin) = origin {
+                loaded.origin = ForkSnapshot::from_archive(origin.manifest, origin.accounts)?;
+            }
+            loaded.changed_accounts.clear();
+            loaded.unsaved = false;
+            loaded.replaced = false;
+            loaded.origin_unsaved = false;
+            loaded.saved_transactions = loaded.transactions.len();
+            loaded.storage = Some((fork_id.clone(), self.storage.clone()));
+        }
+        forks.insert(fork_id.clone(), fork.clone());
+
+        info!(
+            "Rehydrated fork {} from storage with {} accounts",
+            fork_id, account_count
+        );
+        Ok(Some(fork))
+    }
+
+    /// Save a fork's changes since the last save
+    async fn save_fork_state(&self, info: &ForkInfo, fork: &mut Fork) -> Result<()> {
+        if !self.storage.persists_state() {
+            return Ok(());
+        }
+        // Lets the interval producer save blocks it produces between requests
+        fork.storage = Some((info.fork_id.clone(), self.storage.clone()));
+        save_fork_state(&*self.storage, info, fork).await
+    }
+
+    /// Save a fork after a mutation. The in-memory state stays authoritative, so failures are
+    /// logged and the fork is rewritten in full on the next save.
+    async fn persist_fork(&self, fork_id: &ForkId, fork: &mut Fork) {
+        persist_fork(&*self.storage, fork_id, fork).await
+    }
+
+    /// Fetch accounts from mainnet recursively, getting all accounts in reverse order of ownership
+    /// Accounts are returned in loading order (program data before programs)
+    ///
+    /// Accounts matching `queries` are fetched with `getProgramAccounts` and resolved along
+    /// with `pubkeys`.
+    ///
+    /// Every batch must be served at the same upstream slot. If batches drift apart, the whole
+    /// account set is fetched again with `minContextSlot` pinned to the newest slot observed.
+    /// Queries are not run again; the accounts they matched are refetched.
+    async fn fetch_mainnet_accounts(
+        &self,
+        upstream: &UpstreamClient,
+        pubkeys: &[String],
+        queries: &[ProgramAccountsQuery],
+        limits: &FetchLimits,
+        options: FetchOptions,
+    ) -> Result<FetchedAccounts> {
+        if pubkeys.is_empty() && queries.is_empty() {
+            return Ok(FetchedAccounts {
+                accounts: Vec::new(),
+                slot: None,
+                matched: Vec::new(),
+                inclusions: HashMap::new(),
+                missing: Vec::new(),
+                failed: Vec::new(),
+            });
+        }
 
-        all_accounts.sort_by_key(|(_, account)| {
-            let is_bpf_owner = account.owner == bpf_loader;
-            match (account.executable, is_bpf_owner) {
-                (false, false) => 0, // Non-executable, non-BPF
-                (false, true) => 1,  // Program data accounts (must come before programs)
-                (true, true) => 2,   // BPF programs (need program data to be set first)
-                (true, false) => 3,  // Other executable accounts
+        let mut fetch = FetchState::new(upstream.clone(), options, limits.clone());
+        for pubkey in pubkeys {
+            fetch.include(
+                pubkey,
+                Inclusion {
+                    reason: InclusionReason::Requested,
+                    dependency_of: None,
+                    depth: 0,
+                },
+            );
+        }
+        let mut matched = Vec::new();
+        for query in queries {
+            let (slot, accounts) = Self::get_program_accounts(upstream, query, &options).await?;
+            fetch.slots.push(slot);
+            let mut fetched = Vec::with_capacity(accounts.len());
+            for (pubkey_str, account) in accounts {
+                if fetch.processed.contains(&pubkey_str) || matched.contains(&pubkey_str) {
+                    continue;
+                }
+                let account = match account {
+                    Ok(account) => account,
+                    Err(e) => {
+                        warn!("Failed to decode account {}: {}", pubkey_str, e);
+                        fetch.failed.push(AccountFailure {
+                            pubkey: pubkey_str,
+                            error: e.to_string(),
+                        });
+                        continue;
+                    }
+                };
+                self.cache
+                    .insert(upstream.id(), pubkey_str.parse()?, &account, slot);
+                fetch.uncached.insert(pubkey_str.clone());
+                fetch.include(
+                    &pubkey_str,
+                    Inclusion {
+                        reason: InclusionReason::ProgramAccounts,
+                        dependency_of: None,
+                        depth: 0,
+                    },
+                );
+                matched.push(pubkey_str.clone());
+                fetched.push((pubkey_str, Some(account)));
+            }
+            self.add_fetched_accounts(fetched, &mut fetch).await?;
+        }
+        self.fetch_accounts_recursive(pubkeys, &mut fetch).await?;
+
+        for attempt in 1..=MAX_SLOT_ATTEMPTS {
+            // Cached programs older than the fresh batches are kept; newer ones pin the refetch
+            let newest = fetch.slots.iter().copied().max().max(fetch.cached_slot);
+            let (min_slot, max_slot) = (
+                fetch
+                    .slots
+                    .iter()
+                    .copied()
+                    .min()
+                    .or(fetch.cached_slot)
+                    .unwrap_or(0),
+                newest.unwrap_or(0),
+            );
+            if min_slot == max_slot {
+                sort_for_loading(&mut fetch.accounts);
+                return Ok(FetchedAccounts {
+                    accounts: fetch.accounts,
+                    slot: newest,
+                    matched,
+                    inclusions: fetch.inclusions,
+                    missing: fetch.missing,
+                    failed: fetch.failed,
+                });
             }
+            if attempt == MAX_SLOT_ATTEMPTS {
+                break;
+            }
+
+            warn!(
+                "Account batches observed slots {}..={}, refetching {} accounts at slot {} or later (attempt {})",
+                min_slot,
+                max_slot,
+                fetch.processed.len(),
+                max_slot,
+                attempt + 1
+            );
+            // Cached accounts are only served again if observed at the pinned slot or later
+            let pubkeys: Vec<String> = fetch.processed.iter().cloned().collect();
+            let uncached = std::mem::take(&mut fetch.uncached);
+            let inclusions = std::mem::take(&mut fetch.inclusions);
+            fetch = FetchState::new(
+                upstream.clone(),
+                FetchOptions {
+                    min_context_slot: Some(max_slot),
+                    ..options
+                },
+                limits.clone(),
+            );
+            fetch.uncached = uncached;
+            // Depths carry over, so the refetch stops at the same dependency level
+            fetch.inclusions = inclusions;
+            self.fetch_accounts_recursive(&pubkeys, &mut fetch).await?;
+        }
+
+        Err(anyhow::anyhow!(
+            "Upstream account batches did not agree on a slot after {} attempts",
+            MAX_SLOT_ATTEMPTS
+        ))
+    }
+
+    /// Fetch one batch with `getMultipleAccounts`. Returns the response slot and account values.
+    async fn get_multiple_accounts(
+        upstream: &UpstreamClient,
+        pubkeys: &[String],
+        options: &FetchOptions,
+    ) -> Result<(u64, Vec<serde_json::Value>)> {
+        let mut config = json!({"encoding": "base64", "commitment": options.commitment.as_str()});
+        if let Some(min_context_slot) = options.min_context_slot {
+            config["minContextSlot"] = json!(min_context_slot);
+        }
+
+        let mut result = upstream
+            .call("getMultipleAccounts", json!([pubkeys, config]))
+            .await?;
+        let slot = result["context"]["slot"].as_u64().ok_or_else(|| {
+            anyhow::anyhow!("Unexpected getMultipleAccounts response: missing context.slot")
+        })?;
+        let serde_json::Value::Array(accounts_data) = result["value"].take() else {
+            return Err(anyhow::anyhow!(
+                "Unexpected getMultipleAccounts response: missing value array"
+            ));
+        };
+        Ok((slot, accounts_data))
+    }
+
+    /// Run a `getProgramAccounts` query. Returns the response slot and the matching accounts,
+    /// or why they could not be decoded.
+    async fn get_program_accounts(
+        upstream: &UpstreamClient,
+        query: &ProgramAccountsQuery,
+        options: &FetchOptions,
+    ) -> Result<(u64, Vec<(String, Result<Account>)>)> {
+        let mut config = json!({
+            "encoding": "base64",
+            "commitment": options.commitment.as_str(),
+            "filters": query.filters,
+            "withContext": true
         });
+        if let Some(min_context_slot) = options.min_context_slot {
+            config["minContextSlot"] = json!(min_context_slot);
+        }
+
+        let mut result = upstream
+            .call("getProgramAccounts", json!([query.program_id, config]))
+            .await?;
+        let slot = result["context"]["slot"].as_u64().ok_or_else(|| {
+            anyhow::anyhow!("Unexpected getProgramAccounts response: missing context.slot")
+        })?;
+        let serde_json::Value::Array(values) = result["value"].take() else {
+            return Err(anyhow::anyhow!(
+                "Unexpected getProgramAccounts response: missing value array"
+            ));
+        };
+
+        let max_accounts = query.max_accounts.unwrap_or(DEFAULT_MAX_PROGRAM_ACCOUNTS);
+        if values.len() > max_accounts {
+            return Err(anyhow::anyhow!(
+                "getProgramAccounts for {} matched {} accounts, more than maxAccounts {}",
+                query.program_id,
+                values.len(),
+                max_accounts
+            ));
+        }
+        info!(
+            "getProgramAccounts for {} matched {} account(s)",
+            query.program_id,
+            values.len()
+        );
+
+        let accounts = values
+            .into_iter()
+            .map(|value| {
+                let pubkey_str = value["pubkey"].as_str().ok_or_else(|| {
+                    anyhow::anyhow!("Unexpected getProgramAccounts response: missing pubkey")
+                })?;
+                let pubkey: Pubkey = pubkey_str.parse()?;
+                let account = Self::parse_account(&pubkey, &value["account"]);
+                Ok((pubkey_str.to_string(), account))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok((slot, accounts))
+    }
+
+    /// Decode an account from a base64-encoded `getMultipleAccounts` value
+    fn parse_account(pubkey: &Pubkey, account_data: &serde_json::Value) -> Result<Account> {
+        let lamports = account_data["lamports"].as_u64().unwrap_or(0);
+        let owner_str = account_data["owner"].as_str().unwrap_or("");
+        let owner: Pubkey = owner_str
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse owner '{}': {}", owner_str, e))?;
+        let executable = account_data["executable"].as_bool().unwrap_or(false);
+        let rent_epoch = account_data["rentEpoch"].as_u64().unwrap_or(0);
+
+        // `[data, "base64"]`; accounts without data have an empty string
+        let data_str = account_data["data"][0].as_str().ok_or_else(|| {
+            anyhow::anyhow!(
+                "data is not a [data, encoding] array: {}",
+                account_data["data"]
+            )
+        })?;
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(data_str)
+            .map_err(|e| anyhow::anyhow!("Failed to decode base64 data: {}", e))?;
+
+        info!(
+            "Final account {}: lamports={}, data_len={}, owner={}, executable={}, rent_epoch={}",
+            pubkey,
+            lamports,
+            data.len(),
+            owner,
+            executable,
+            rent_epoch
+        );
 
-        Ok(all_accounts)
+        Ok(Account {
+            lamports,
+            data,
+            owner,
+            executable,
+            rent_epoch,
+        })
     }
 
     /// Recursive helper function to fetch accounts and their dependencies
     async fn fetch_accounts_recursive(
         &self,
         pubkeys: &[String],
-        all_accounts: &mut Vec<(Pubkey, Account)>,
-        processed_pubkeys: &mut std::collections::HashSet<String>,
+        fetch: &mut FetchState,
     ) -> Result<()> {
         // Filter out already processed pubkeys
         let new_pubkeys: Vec<String> = pubkeys
             .iter()
-            .filter(|pk| !processed_pubkeys.contains(*pk))
+            .filter(|pk| !fetch.processed.contains(*pk))
             .cloned()
             .collect();
 
@@ -189,250 +2288,411 @@ impl ForkManager {
             return Ok(());
         }
 
-        let client = reqwest::Client::new();
-        let request_body = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "getMultipleAccounts",
-            "params": [
-                new_pubkeys,
-                {"encoding": "base64", "commitment": "confirmed"}
-            ]
-        });
+        // Serve what the cache has, unless fresh accounts were requested
+        let mut fetched: Vec<(String, Option<Account>)> = Vec::with_capacity(new_pubkeys.len());
+        let mut to_fetch = Vec::new();
+        for pubkey_str in new_pubkeys {
+            let cached = if fetch.options.fresh || fetch.uncached.contains(&pubkey_str) {
+                None
+            } else {
+                let pubkey: Pubkey = pubkey_str.parse()?;
+                self.cache
+                    .get(fetch.upstream.id(), &pubkey, fetch.options.min_context_slot)
+            };
+            match cached {
+                Some((account, slot)) => {
+                    if dependencies::LOADERS.contains(&account.owner) {
+                        fetch.cached_slot = fetch.cached_slot.max(Some(slot));
+                    } else {
+                        fetch.slots.push(slot);
+                    }
+                    fetched.push((pubkey_str, Some(account)));
+                }
+                None => to_fetch.push(pubkey_str),
+            }
+        }
+        fetch.uncached.extend(to_fetch.iter().cloned());
+        if !fetched.is_empty() {
+            debug!("Serving {} account(s) from cache", fetched.len());
+        }
 
-        let response = client
-            .post(&self.solana_rpc)
-            .json(&request_body)
-            .send()
-            .await?;
+        // Chunks are fetched concurrently; `buffered` keeps responses in request order
+        let (upstream, options) = (&fetch.upstream, fetch.options);
+        let batches: Vec<(u64, Vec<serde_json::Value>)> = stream::iter(
+            to_fetch
+                .chunks(MAX_ACCOUNTS_PER_REQUEST)
+                .map(<[String]>::to_vec),
+        )
+        .map(|chunk| async move { Self::get_multiple_accounts(upstream, &chunk, &options).await })
+        .buffered(self.fetch_concurrency)
+        .try_collect()
+        .await?;
+
+        let mut received = 0;
+        for (chunk, (slot, values)) in to_fetch.chunks(MAX_ACCOUNTS_PER_REQUEST).zip(batches) {
+            fetch.slots.push(slot);
+            received += values.len();
+            for (pubkey_str, account_data) in chunk.iter().zip(values) {
+                if account_data.is_null() {
+                    warn!("Account {} is null in API response", pubkey_str);
+                    fetch.missing.push(pubkey_str.clone());
+                    fetched.push((pubkey_str.clone(), None));
+                    continue;
+                }
+                let pubkey: Pubkey = pubkey_str.parse()?;
+                let account = match Self::parse_account(&pubkey, &account_data) {
+                    Ok(account) => account,
+                    Err(e) => {
+                        warn!("Failed to decode account {}: {}", pubkey_str, e);
+                        fetch.failed.push(AccountFailure {
+                            pubkey: pubkey_str.clone(),
+                            error: e.to_string(),
+                        });
+                        fetched.push((pubkey_str.clone(), None));
+                        continue;
+                    }
+                };
+                self.cache
+                    .insert(fetch.upstream.id(), pubkey, &account, slot);
+                fetched.push((pubkey_str.clone(), Some(account)));
+            }
+        }
 
-        let data: serde_json::Value = response.json().await?;
-        let accounts_data = data["result"]["value"].as_array().ok_or_else(|| {
-            anyhow::anyhow!("Invalid response format: missing result.value array")
-        })?;
+        info!("Received {} account(s) from API", received);
 
-        info!("Received {} account(s) from API", accounts_data.len());
+        self.add_fetched_accounts(fetched, fetch).await
+    }
 
+    /// Add fetched accounts in loading order, then fetch their owners and program data
+    async fn add_fetched_accounts(
+        &self,
+        fetched: Vec<(String, Option<Account>)>,
+        fetch: &mut FetchState,
+    ) -> Result<()> {
         let mut non_executable_accounts = Vec::new();
         let mut executable_accounts = Vec::new();
         let mut owner_pubkeys = Vec::new();
-        let mut program_data_accounts = Vec::new(); // For BPF Upgradeable programs
+        // Program data, lookup table entries, mints, transfer hooks and IDL accounts
+        let mut dependent_pubkeys = Vec::new();
 
-        // BPF Upgradeable Loader program ID
-        let bpf_upgradeable_loader = "BPFLoaderUpgradeab1e11111111111111111111111"
-            .parse::<Pubkey>()
-            .unwrap();
-
-        for (i, account_data) in accounts_data.iter().enumerate() {
-            if account_data.is_null() {
-                warn!(
-                    "Account {} at index {} is null in API response",
-                    new_pubkeys.get(i).unwrap_or(&"unknown".to_string()),
-                    i
-                );
-                processed_pubkeys.insert(new_pubkeys[i].clone());
+        for (pubkey_str, account) in fetched {
+            fetch.processed.insert(pubkey_str.clone());
+            let Some(account) = account else {
                 continue;
+            };
+            let pubkey: Pubkey = pubkey_str.parse()?;
+            let (owner, executable) = (account.owner, account.executable);
+            fetch.bytes += account.data.len() as u64;
+
+            let depth = fetch.depth(&pubkey_str);
+            let mut account_dependencies = Vec::new();
+            if fetch.follows_dependencies(depth) {
+                // The system program is built in
+                if owner != Pubkey::default() {
+                    account_dependencies.push((owner, InclusionReason::Owner));
+                }
+                account_dependencies.extend(dependencies::account_dependencies(&pubkey, &account));
+                if fetch.options.idl && dependencies::is_program(&account) {
+                    account_dependencies
+                        .push((dependencies::idl_address(&pubkey), InclusionReason::Idl));
+                }
             }
-
-            let pubkey: Pubkey = new_pubkeys[i].parse()?;
-            let lamports = account_data["lamports"].as_u64().unwrap_or(0);
-            let owner_str = account_data["owner"].as_str().unwrap_or("");
-            let owner: Pubkey = owner_str
-                .parse()
-                .map_err(|e| anyhow::anyhow!("Failed to parse owner '{}': {}", owner_str, e))?;
-            let executable = account_data["executable"].as_bool().unwrap_or(false);
-            let rent_epoch = account_data["rentEpoch"].as_u64().unwrap_or(0);
-
-            let data = if let Some(data_array) = account_data["data"].as_array() {
-                if data_array.len() >= 1 {
-                    let data_str = data_array[0].as_str().unwrap_or("");
-                    info!(
-                        "Account {} data string from API (base64): '{}'",
-                        pubkey, data_str
-                    );
-                    if data_str.is_empty() {
-                        warn!("Account {} data string is EMPTY!", pubkey);
-                        Vec::new()
-                    } else {
-                        match base64::engine::general_purpose::STANDARD.decode(data_str) {
-                            Ok(decoded) => {
-                                info!(
-                                    "Account {} decoded data length: {} bytes",
-                                    pubkey,
-                                    decoded.len()
-                                );
-                                decoded
-                            }
-                            Err(e) => {
-                                error!(
-                                    "Failed to decode base64 data for account {}: {}",
-                                    pubkey, e
-                                );
-                                Vec::new()
-                            }
-                        }
-                    }
-                } else {
-                    warn!("Account {} data array is empty!", pubkey);
-                    Vec::new()
+            for (dependency, reason) in account_dependencies {
+                let dependency = dependency.to_string();
+                if fetch.processed.contains(&dependency)
+                    || fetch.limits.exclude.contains(&dependency)
+                {
+                    continue;
                 }
-            } else {
-                warn!(
-                    "Account {} data field is not an array: {:?}",
-                    pubkey, account_data["data"]
+                fetch.include(
+                    &dependency,
+                    Inclusion {
+                        reason,
+                        dependency_of: Some(pubkey),
+                        depth: depth + 1,
+                    },
                 );
-                Vec::new()
-            };
-
-            info!(
-                "Final account {}: lamports={}, data_len={}, owner={}, executable={}, rent_epoch={}",
-                pubkey, lamports, data.len(), owner, executable, rent_epoch
-            );
-
-            // Check if this is a BPF Upgradeable program and extract program data account
-            if executable && owner == bpf_upgradeable_loader && data.len() >= 36 {
-                // BPF Upgradeable Program account structure:
-                // - Bytes 0-3: Account discriminator (3 for Program account)
-                // - Bytes 4-35: ProgramData account pubkey (32 bytes)
-                let program_data_bytes: [u8; 32] = data[4..36].try_into().unwrap();
-                let program_data_pubkey = Pubkey::new_from_array(program_data_bytes);
-                let program_data_str = program_data_pubkey.to_string();
-
-                if !processed_pubkeys.contains(&program_data_str) {
-                    info!(
-                        "Found BPF Upgradeable program {}, adding program data account {}",
-                        pubkey, program_data_pubkey
-                    );
-                    program_data_accounts.push(program_data_str);
+                if reason == InclusionReason::Owner {
+                    owner_pubkeys.push(dependency);
+                } else {
+                    debug!("Account {} depends on {}", pubkey, dependency);
+                    dependent_pubkeys.push(dependency);
                 }
             }
 
-            let account = Account {
-                lamports,
-                data,
-                owner,
-                executable,
-                rent_epoch,
-            };
-
             // Separate accounts by executable status for reverse order processing
             if executable {
                 executable_accounts.push((pubkey, account));
             } else {
                 non_executable_accounts.push((pubkey, account));
             }
-
-            // Collect owner pubkeys for recursive fetching
-            if owner.to_string() != "11111111111111111111111111111111"
-                && !processed_pubkeys.contains(&owner.to_string())
-            {
-                owner_pubkeys.push(owner.to_string());
-            }
-
-            processed_pubkeys.insert(new_pubkeys[i].clone());
         }
 
         // Process in reverse order: non-executable accounts first (in reverse), then executable accounts (in reverse)
         for (pubkey, account) in non_executable_accounts.into_iter().rev() {
-            all_accounts.push((pubkey, account));
+            fetch.accounts.push((pubkey, account));
         }
 
         for (pubkey, account) in executable_accounts.into_iter().rev() {
-            all_accounts.push((pubkey, account));
+            fetch.accounts.push((pubkey, account));
         }
 
-        // Recursively fetch owner accounts
-        if !owner_pubkeys.is_empty() {
-            Box::pin(self.fetch_accounts_recursive(
-                &owner_pubkeys,
-                all_accounts,
-                processed_pubkeys,
-            ))
-            .await?;
+        if let Some(max_bytes) = fetch.limits.max_bytes.filter(|max| fetch.bytes > *max) {
+            return Err(anyhow::anyhow!(
+                "Fetched accounts total {} bytes, more than maxBytes {}",
+                fetch.bytes,
+                max_bytes
+            ));
         }
 
-        // Recursively fetch program data accounts for BPF Upgradeable programs
-        if !program_data_accounts.is_empty() {
-            info!(
-                "Fetching {} program data account(s) for BPF Upgradeable programs",
-                program_data_accounts.len()
-            );
-            Box::pin(self.fetch_accounts_recursive(
-                &program_data_accounts,
-                all_accounts,
-                processed_pubkeys,
-            ))
-            .await?;
+        // Recursively fetch owner accounts and other dependencies together, so both levels
+        // share the concurrent batches
+        if !dependent_pubkeys.is_empty() {
+            info!("Fetching {} dependent account(s)", dependent_pubkeys.len());
+        }
+        let mut next_level = owner_pubkeys;
+        next_level.extend(dependent_pubkeys);
+        next_level.sort();
+        next_level.dedup();
+        if !next_level.is_empty() {
+            Box::pin(self.fetch_accounts_recursive(&next_level, fetch)).await?;
         }
 
         Ok(())
     }
 
-    /// Initialize the fork's chain context from the upstream RPC (slot only; blockhash best-effort).
-    async fn initialize_chain_context(&self, svm: &mut LiteSVM) -> Result<()> {
-        // Fetch latest blockhash (for context.slot) and getSlot explicitly as fallback
-        let client = reqwest::Client::new();
-        let lb_resp = client
-            .post(&self.solana_rpc)
-            .json(&json!({
-                "jsonrpc": "2.0",
-                "id": 1,
-                "method": "getLatestBlockhash",
-                "params": [{"commitment": "confirmed"}]
-            }))
-            .send()
-            .await?;
-        let lb_json: serde_json::Value = lb_resp.json().await?;
-        let mut slot = lb_json["result"]["context"]["slot"].as_u64().unwrap_or(0);
-        if slot == 0 {
-            let slot_resp = client
-                .post(&self.solana_rpc)
-                .json(&json!({"jsonrpc":"2.0","id":2,"method":"getSlot"}))
-                .send()
-                .await?;
-            let slot_json: serde_json::Value = slot_resp.json().await?;
-            slot = slot_json["result"].as_u64().unwrap_or(0);
+    /// Sync the fork's chain context from the upstream RPC: the `Clock` (slot, block time and
+    /// epoch), `EpochSchedule`, `SlotHashes` and the latest blockhash. Parts upstream can't
+    /// provide keep liteSVM's defaults and are reported in the returned sync status.
+    ///
+    /// `source_slot` is the slot the accounts were fetched at; without it the fork starts at
+    /// upstream's latest slot.
+    async fn initialize_chain_context(
+        &self,
+        upstream: &UpstreamClient,
+        svm: &mut LiteSVM,
+        source_slot: Option<u64>,
+        options: FetchOptions,
+    ) -> ChainContext {
+        let mut errors = Vec::new();
+        let mut config = json!({"commitment": options.commitment.as_str()});
+        if let Some(min_context_slot) = options.min_context_slot {
+            config["minContextSlot"] = json!(min_context_slot);
         }
 
-        // Set Clock sysvar slot to match upstream
-        if slot > 0 {
-            let mut clock: Clock = svm.get_sysvar::<Clock>();
-            clock.slot = slot;
-            svm.set_sysvar::<Clock>(&clock);
+        // The latest blockhash; its context slot is the fork's slot if nothing was fetched
+        let mut blockhash = None;
+        let mut latest_slot = None;
+        match upstream.call("getLatestBlockhash", json!([config])).await {
+            Ok(latest) => {
+                latest_slot = latest["context"]["slot"].as_u64().filter(|slot| *slot > 0);
+                blockhash = latest["value"]["blockhash"]
+                    .as_str()
+                    .and_then(|hash| hash.parse::<Hash>().ok());
+                if blockhash.is_none() {
+                    errors.push("getLatestBlockhash returned no blockhash".to_string());
+                }
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+        let slot = match source_slot.or(latest_slot) {
+            Some(slot) => Some(slot),
+            None if blockhash.is_some() => upstream
+                .call("getSlot", json!([config]))
+                .await
+                .ok()
+                .and_then(|slot| slot.as_u64())
+                .filter(|slot| *slot > 0),
+            // Upstream is unreachable
+            None => None,
+        };
+        let Some(slot) = slot else {
+            errors.push("No upstream slot to start from".to_string());
+            return ChainContext {
+                slot: None,
+                blockhash,
+                sync: ChainSync {
+                    synced: false,
+                    errors,
+                },
+            };
+        };
+
+        let epoch_schedule = match Self::get_epoch_schedule(upstream).await {
+            Ok(epoch_schedule) => {
+                svm.set_sysvar::<EpochSchedule>(&epoch_schedule);
+                epoch_schedule
+            }
+            Err(e) => {
+                errors.push(e.to_string());
+                svm.get_sysvar::<EpochSchedule>()
+            }
+        };
+
+        let mut clock: Clock = svm.get_sysvar::<Clock>();
+        clock.slot = slot;
+        clock.leader_schedule_epoch = epoch_schedule.get_leader_schedule_epoch(slot);
+        let (epoch, slot_index) = match Self::get_epoch_info(upstream, &config).await {
+            // Upstream reports its current epoch; the fork's slot may be in an earlier one
+            Ok((epoch, first_slot)) if slot >= first_slot => (epoch, slot - first_slot),
+            Ok(_) => epoch_schedule.get_epoch_and_slot_index(slot),
+            Err(e) => {
+                errors.push(e.to_string());
+                epoch_schedule.get_epoch_and_slot_index(slot)
+            }
+        };
+        clock.epoch = epoch;
+        match Self::get_block_time(upstream, slot).await {
+            Ok(unix_timestamp) => {
+                clock.unix_timestamp = unix_timestamp;
+                // Estimated from the slot time, as the epoch's first block may be skipped
+                clock.epoch_start_timestamp =
+                    unix_timestamp - (slot_index as i64 * SLOT_DURATION_MS) / 1000;
+            }
+            Err(e) => errors.push(e.to_string()),
+        }
+        svm.set_sysvar::<Clock>(&clock);
+
+        match Self::get_slot_hashes(upstream, slot, &options).await {
+            Ok(slot_hashes) => svm.set_sysvar::<SlotHashes>(&slot_hashes),
+            Err(e) => errors.push(e.to_string()),
+        }
+
+        ChainContext {
+            slot: Some(slot),
+            blockhash,
+            sync: ChainSync {
+                synced: errors.is_empty(),
+                errors,
+            },
         }
-        Ok(())
+    }
+
+    async fn get_epoch_schedule(upstream: &UpstreamClient) -> Result<EpochSchedule> {
+        let result = upstream.call("getEpochSchedule", json!([])).await?;
+        serde_json::from_value(result)
+            .map_err(|e| anyhow::anyhow!("Unexpected getEpochSchedule response: {}", e))
+    }
+
+    /// Upstream's current epoch and the first slot in it
+    async fn get_epoch_info(
+        upstream: &UpstreamClient,
+        config: &serde_json::Value,
+    ) -> Result<(u64, u64)> {
+        let result = upstream.call("getEpochInfo", json!([config])).await?;
+        match (
+            result["epoch"].as_u64(),
+            result["absoluteSlot"].as_u64(),
+            result["slotIndex"].as_u64(),
+        ) {
+            (Some(epoch), Some(absolute_slot), Some(slot_index)) => {
+                Ok((epoch, absolute_slot.saturating_sub(slot_index)))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Unexpected getEpochInfo response: {}",
+                result
+            )),
+        }
+    }
+
+    async fn get_block_time(upstream: &UpstreamClient, slot: u64) -> Result<i64> {
+        let result = upstream.call("getBlockTime", json!([slot])).await?;
+        result
+            .as_i64()
+            .ok_or_else(|| anyhow::anyhow!("getBlockTime has no block time for slot {}", slot))
+    }
+
+    /// Upstream's `SlotHashes` sysvar, without entries for slots after `slot`
+    async fn get_slot_hashes(
+        upstream: &UpstreamClient,
+        slot: u64,
+        options: &FetchOptions,
+    ) -> Result<SlotHashes> {
+        let sysvar = solana_sysvar::slot_hashes::ID;
+        let (_, mut values) =
+            Self::get_multiple_accounts(upstream, &[sysvar.to_string()], options).await?;
+        let value = values.pop().unwrap_or_default();
+        if value.is_null() {
+            return Err(anyhow::anyhow!("SlotHashes sysvar not found upstream"));
+        }
+        let account = Self::parse_account(&sysvar, &value)?;
+        let slot_hashes: SlotHashes = bincode::deserialize(&account.data)
+            .map_err(|e| anyhow::anyhow!("Invalid SlotHashes sysvar: {}", e))?;
+        let entries: Vec<_> = slot_hashes
+            .slot_hashes()
+            .iter()
+            .filter(|(hash_slot, _)| *hash_slot < slot)
+            .copied()
+            .collect();
+        Ok(SlotHashes::new(&entries))
     }
 
     /// Process RPC methods
-    async fn process_rpc_method(&self, svm: &mut LiteSVM, req: JsonRpcRequest) -> JsonRpcResponse {
-        let clock: Clock = svm.get_sysvar::<Clock>();
+    async fn process_rpc_method(
+        &self,
+        fork: &mut Fork,
+        info: &ForkInfo,
+        req: JsonRpcRequest,
+    ) -> JsonRpcResponse {
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
         let current_slot = clock.slot;
 
+        if fork.lazy && matches!(req.method.as_str(), "getBalance" | "getAccountInfo") {
+            let pubkey = req
+                .params
+                .as_ref()
+                .and_then(|p| p[0].as_str())
+                .and_then(|pubkey| pubkey.parse::<Pubkey>().ok());
+            if let Some(pubkey) = pubkey {
+                if let Err(e) = self.fetch_absent_accounts(fork, info, [pubkey]).await {
+                    return Self::into_response(
+                        req.id,
+                        Err(anyhow::anyhow!("Failed to fetch account {}: {}", pubkey, e)),
+                    );
+                }
+            }
+        }
+
         let result = match req.method.as_str() {
-            "getBalance" => self.rpc_get_balance(svm, &req.params),
-            "getAccountInfo" => self.rpc_get_account_info(svm, &req.params),
-            "sendTransaction" => self.rpc_send_transaction(svm, &req.params),
-            "setAccount" => self.rpc_set_account(svm, &req.params).await,
+            "getBalance" => self.rpc_get_balance(&fork.svm, &req.params),
+            "getAccountInfo" => self.rpc_get_account_info(&fork.svm, &req.params),
+            "sendTransaction" => self.rpc_send_transaction(fork, info, &req.params).await,
+            "setAccount" => self.rpc_set_account(fork, info, &req.params).await,
+            "sim_refreshAccounts" => self.rpc_refresh_accounts(fork, info, &req.params).await,
+            "sim_setOraclePrice" => self.rpc_set_oracle_price(fork, &req.params),
+            "sim_createNonceAccount" => self.rpc_create_nonce_account(fork, &req.params),
+            "sim_mine" => self.rpc_mine(fork, &req.params),
+            "sim_setBlockProduction" => self.rpc_set_block_production(fork, &req.params),
+            "sim_snapshot" => {
+                let id = fork.snapshot();
+                info!("Created snapshot {} at slot {}", id, current_slot);
+                Ok(json!({"context": {"slot": current_slot}, "value": id}))
+            }
+            "sim_revert" => self.rpc_revert(fork, &req.params),
             "getLatestBlockhash" => Ok(json!({
                 "context": {"slot": current_slot},
                 "value": {
-                    "blockhash": svm.latest_blockhash().to_string(),
+                    "blockhash": fork.latest_blockhash().to_string(),
                     "lastValidBlockHeight": current_slot
                 }
             })),
             _ => Err(anyhow::anyhow!("Method not supported")),
         };
 
+        Self::into_response(req.id, result)
+    }
+
+    fn into_response(id: serde_json::Value, result: Result<serde_json::Value>) -> JsonRpcResponse {
         match result {
             Ok(res) => JsonRpcResponse {
                 jsonrpc: "2.0".to_string(),
-                id: req.id,
+                id,
                 result: Some(res),
                 error: None,
             },
             Err(e) => JsonRpcResponse {
                 jsonrpc: "2.0".to_string(),
-                id: req.id,
+                id,
                 result: None,
                 error: Some(JsonRpcError {
                     code: -32603,
@@ -442,6 +2702,68 @@ impl ForkManager {
         }
     }
 
+    /// Produce `n` blocks (default 1)
+    fn rpc_mine(
+        &self,
+        fork: &mut Fork,
+        params: &Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let blocks = match params.as_ref().and_then(|p| p.get(0)) {
+            Some(value) => value.as_u64().filter(|n| *n > 0).ok_or_else(|| {
+                anyhow::anyhow!("Invalid params: block count must be a positive integer")
+            })?,
+            None => 1,
+        };
+
+        fork.produce_blocks(blocks);
+
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
+        Ok(json!({
+            "context": {"slot": clock.slot},
+            "value": {
+                "slot": clock.slot,
+                "unixTimestamp": clock.unix_timestamp,
+                "blockhash": fork.latest_blockhash().to_string()
+            }
+        }))
+    }
+
+    fn rpc_revert(
+        &self,
+        fork: &mut Fork,
+        params: &Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let id = params
+            .as_ref()
+            .and_then(|p| p.get(0))
+            .and_then(|id| id.as_u64())
+            .ok_or_else(|| anyhow::anyhow!("Invalid params: expected 1 param (snapshotId)"))?;
+
+        fork.revert(id)?;
+
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
+        info!("Reverted to snapshot {} at slot {}", id, clock.slot);
+        Ok(json!({"context": {"slot": clock.slot}, "value": true}))
+    }
+
+    fn rpc_set_block_production(
+        &self,
+        fork: &mut Fork,
+        params: &Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let mode = params
+            .as_ref()
+            .and_then(|p| p.get(0))
+            .ok_or_else(|| anyhow::anyhow!("Invalid params: expected 1 param (blockProduction)"))?;
+        let mode: BlockProduction = serde_json::from_value(mode.clone())
+            .map_err(|e| anyhow::anyhow!("Failed to parse block production mode: {}", e))?;
+
+        fork.set_block_production(mode)?;
+
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
+        Ok(json!({"context": {"slot": clock.slot}, "value": mode}))
+    }
+
     fn rpc_get_balance(
         &self,
         svm: &LiteSVM,
@@ -509,9 +2831,10 @@ impl ForkManager {
         }
     }
 
-    fn rpc_send_transaction(
+    async fn rpc_send_transaction(
         &self,
-        svm: &mut LiteSVM,
+        fork: &mut Fork,
+        info: &ForkInfo,
         params: &Option<serde_json::Value>,
     ) -> Result<serde_json::Value> {
         let tx_data = params
@@ -519,22 +2842,198 @@ impl ForkManager {
             .and_then(|p| p[0].as_str())
             .ok_or_else(|| anyhow::anyhow!("Missing transaction"))?;
 
+        // Legacy transactions deserialize as versioned transactions too
         let tx_bytes = base64::engine::general_purpose::STANDARD.decode(tx_data)?;
-        let transaction: Transaction = bincode::deserialize(&tx_bytes)?;
-
-        let result = svm
-            .send_transaction(transaction)
-            .map_err(|e| anyhow::anyhow!("Failed to send transaction: {:#?}", e))?;
+        let transaction: VersionedTransaction = bincode::deserialize(&tx_bytes)?;
 
-        // Increment slot after transaction
-        Self::increment_slot(svm);
+        if fork.lazy {
+            self.fetch_transaction_accounts(fork, info, &transaction.message)
+                .await?;
+        }
 
+        let result = Self::execute_transaction(fork, transaction, true)?;
+        let result = result.map_err(|e| anyhow::anyhow!("Failed to send transaction: {:#?}", e))?;
         Ok(json!(result.signature.to_string()))
     }
 
+    /// Execute a transaction on the fork and record it. With `check_blockhash` it must be
+    /// signed against one of the fork's recent blockhashes or be a durable nonce transaction.
+    fn execute_transaction(
+        fork: &mut Fork,
+        transaction: VersionedTransaction,
+        check_blockhash: bool,
+    ) -> Result<litesvm::types::TransactionResult> {
+        let durable_nonce = Self::durable_nonce_accounts(&transaction.message);
+        if let Some((nonce_pubkey, authority)) = durable_nonce {
+            Self::prepare_durable_nonce(fork, &transaction, &nonce_pubkey, &authority)?;
+        }
+        let expired = check_blockhash
+            && durable_nonce.is_none()
+            && !fork
+                .recent_blockhashes
+                .contains(transaction.message.recent_blockhash());
+
+        // Fee payer and writable accounts are the only accounts a transaction can change
+        let message = &transaction.message;
+        let mut writable = (0..message.static_account_keys().len())
+            .filter(|i| message.is_maybe_writable(*i, None))
+            .map(|i| message.static_account_keys()[i])
+            .collect::<Vec<_>>();
+        writable.extend(lookup_table_addresses(|key| fork.svm.get_account(key), message).0);
+
+        // Transactions rejected before execution report a default signature
+        let signature = transaction.signatures.first().copied().unwrap_or_default();
+        let slot = fork.svm.get_sysvar::<Clock>().slot;
+        let result = if expired {
+            Err(litesvm::types::FailedTransactionMetadata {
+                err: TransactionError::BlockhashNotFound,
+                meta: Default::default(),
+            })
+        } else {
+            fork.svm.send_transaction(transaction)
+        };
+        fork.mark_changed(writable);
+        let mut record = TransactionRecord::new(slot, &result);
+        record.signature = signature.to_string();
+        fork.transactions.push(record);
+
+        if let (Ok(_), Some((nonce_pubkey, _))) = (&result, durable_nonce) {
+            Self::advance_durable_nonce(fork, &nonce_pubkey)?;
+        }
+        if result.is_ok() && fork.block_production == BlockProduction::PerTransaction {
+            fork.advance_slots(1);
+        }
+
+        Ok(result)
+    }
+
+    /// Execute a transaction signed against another chain's blockhash, e.g. one fetched from
+    /// upstream, without checking its blockhash
+    fn replay_transaction(
+        fork: &mut Fork,
+        transaction: VersionedTransaction,
+    ) -> Result<TransactionRecord> {
+        // The outcome is recorded either way; only a failure to prepare the transaction is an error
+        Self::execute_transaction(fork, transaction, false).map(|_| ())?;
+        Ok(fork
+            .transactions
+            .last()
+            .cloned()
+            .expect("executed transaction is recorded"))
+    }
+
+    /// Returns the nonce account and authority if the first instruction is `AdvanceNonceAccount`
+    fn durable_nonce_accounts(message: &VersionedMessage) -> Option<(Pubkey, Pubkey)> {
+        let system_program = Pubkey::default();
+        let account_keys = message.static_account_keys();
+        let ix = message.instructions().first()?;
+        let program_id = account_keys.get(ix.program_id_index as usize)?;
+
+        // SystemInstruction::AdvanceNonceAccount discriminator
+        if *program_id != system_program || ix.data.get(..4) != Some(&[4, 0, 0, 0][..]) {
+            return None;
+        }
+
+        // Accounts: [nonce account, RecentBlockhashes sysvar, nonce authority]
+        let nonce_pubkey = account_keys.get(*ix.accounts.first()? as usize)?;
+        let authority = account_keys.get(*ix.accounts.get(2)? as usize)?;
+        Some((*nonce_pubkey, *authority))
+    }
+
+    /// Validate a durable nonce transaction against fork state and make sure the nonce can advance.
+    ///
+    /// A nonce advanced in the current block holds the durable nonce of the fork's blockhash and
+    /// can't be used again until a block is produced, the same as on a validator.
+    fn prepare_durable_nonce(
+        fork: &Fork,
+        transaction: &VersionedTransaction,
+        nonce_pubkey: &Pubkey,
+        authority: &Pubkey,
+    ) -> Result<()> {
+        let nonce_account = fork
+            .svm
+            .get_account(nonce_pubkey)
+            .ok_or_else(|| anyhow::anyhow!("Nonce account {} not found in fork", nonce_pubkey))?;
+
+        if nonce_account.owner != Pubkey::default() {
+            return Err(anyhow::anyhow!(
+                "Nonce account {} is not owned by the system program",
+                nonce_pubkey
+            ));
+        }
+
+        let versions: NonceVersions = bincode::deserialize(&nonce_account.data)
+            .map_err(|e| anyhow::anyhow!("Invalid nonce account {}: {}", nonce_pubkey, e))?;
+        let data = match versions.state() {
+            NonceState::Initialized(data) => data.clone(),
+            NonceState::Uninitialized => {
+                return Err(anyhow::anyhow!(
+                    "Nonce account {} is not initialized",
+                    nonce_pubkey
+                ))
+            }
+        };
+
+        let message = &transaction.message;
+        if data.blockhash() != *message.recent_blockhash() {
+            return Err(anyhow::anyhow!(
+                "Transaction nonce {} does not match stored nonce {} of account {}",
+                message.recent_blockhash(),
+                data.blockhash(),
+                nonce_pubkey
+            ));
+        }
+
+        if data.authority != *authority
+            || !message
+                .static_account_keys()
+                .iter()
+                .take(message.header().num_required_signatures as usize)
+                .any(|signer| signer == authority)
+        {
+            return Err(anyhow::anyhow!(
+                "Nonce authority {} must sign the transaction",
+                data.authority
+            ));
+        }
+
+        if data.durable_nonce == DurableNonce::from_blockhash(&fork.latest_blockhash()) {
+            return Err(anyhow::anyhow!(
+                "Nonce account {} already used in this block; wait for the next block or \
+                 produce one with sim_mine before using it again",
+                nonce_pubkey
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Store the durable nonce of the fork's blockhash in a nonce account after its
+    /// transaction succeeded. liteSVM advances nonces against the transaction's own blockhash,
+    /// the stored nonce, rather than the fork's.
+    fn advance_durable_nonce(fork: &mut Fork, nonce_pubkey: &Pubkey) -> Result<()> {
+        let Some(mut account) = fork.svm.get_account(nonce_pubkey) else {
+            return Ok(());
+        };
+        let versions: NonceVersions = bincode::deserialize(&account.data)
+            .map_err(|e| anyhow::anyhow!("Invalid nonce account {}: {}", nonce_pubkey, e))?;
+        // The transaction may have closed or withdrawn the nonce account
+        let NonceState::Initialized(data) = versions.state() else {
+            return Ok(());
+        };
+        let state = NonceState::new_initialized(
+            &data.authority,
+            DurableNonce::from_blockhash(&fork.latest_blockhash()),
+            data.get_lamports_per_signature(),
+        );
+        account.data = bincode::serialize(&NonceVersions::new(state))?;
+        fork.set_account(*nonce_pubkey, account)
+    }
+
     async fn rpc_set_account(
         &self,
-        svm: &mut LiteSVM,
+        fork: &mut Fork,
+        info: &ForkInfo,
         params: &Option<serde_json::Value>,
     ) -> Result<serde_json::Value> {
         let params_array = params
@@ -554,9 +3053,9 @@ impl ForkManager {
                 .map_err(|e| anyhow::anyhow!("Failed to parse account data: {}", e))?;
 
             let account = account_data.to_account()?;
-            svm.set_account(pubkey, account)?;
+            fork.set_account(pubkey, account)?;
 
-            let clock: Clock = svm.get_sysvar::<Clock>();
+            let clock: Clock = fork.svm.get_sysvar::<Clock>();
             Ok(json!({"context": {"slot": clock.slot}, "value": null}))
         } else if params_array.len() == 1 {
             // Fetch from mainnet
@@ -565,12 +3064,22 @@ impl ForkManager {
                 .ok_or_else(|| anyhow::anyhow!("Invalid pubkey"))?
                 .parse()?;
 
-            let accounts = self.fetch_mainnet_accounts(&[pubkey.to_string()]).await?;
-            let clock: Clock = svm.get_sysvar::<Clock>();
+            let fetched = self
+                .fetch_mainnet_accounts(
+                    &self.upstream(info)?,
+                    &[pubkey.to_string()],
+                    &[],
+                    &FetchLimits::default(),
+                    FetchOptions::for_fork(info),
+                )
+                .await?;
+            fetched.ensure_decoded()?;
+            let accounts = fetched.accounts;
+            let clock: Clock = fork.svm.get_sysvar::<Clock>();
 
             // Set all fetched accounts (includes dependencies)
             for (pk, account) in accounts {
-                svm.set_account(pk, account)?;
+                fork.set_account(pk, account)?;
             }
 
             Ok(json!({"context": {"slot": clock.slot}, "value": null}))
@@ -580,6 +3089,276 @@ impl ForkManager {
             ))
         }
     }
+
+    /// Pull the latest upstream state of the listed accounts, or of every account untouched
+    /// since creation, resolving local modifications with the requested policy
+    async fn rpc_refresh_accounts(
+        &self,
+        fork: &mut Fork,
+        info: &ForkInfo,
+        params: &Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let params_array = params
+            .as_ref()
+            .and_then(|p| p.as_array())
+            .filter(|p| !p.is_empty() && p.len() <= 2)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid params: expected 1 param (pubkeys or \"untouched\") or 2 params (pubkeys or \"untouched\", options)"
+                )
+            })?;
+
+        let untouched = params_array[0].as_str() == Some("untouched");
+        let mut pubkeys: Vec<Pubkey> = match &params_array[0] {
+            serde_json::Value::String(target) if target == "untouched" => fork.untouched_accounts(),
+            serde_json::Value::Array(items) => items
+                .iter()
+                .map(|item| {
+                    let pubkey = item
+                        .as_str()
+                        .ok_or_else(|| anyhow::anyhow!("Invalid pubkey: {}", item))?;
+                    pubkey
+                        .parse()
+                        .map_err(|e| anyhow::anyhow!("Invalid pubkey '{}': {}", pubkey, e))
+                })
+                .collect::<Result<_>>()?,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Invalid params: expected an array of pubkeys or \"untouched\""
+                ))
+            }
+        };
+        pubkeys.sort();
+        pubkeys.dedup();
+
+        let policy: RefreshPolicy = match params_array.get(1).map(|options| &options["policy"]) {
+            Some(serde_json::Value::Null) | None => RefreshPolicy::default(),
+            Some(value) => serde_json::from_value(value.clone())
+                .map_err(|e| anyhow::anyhow!("Invalid policy: {}", e))?,
+        };
+
+        // Only the accounts themselves are refreshed, never their dependencies
+        let requested: Vec<String> = pubkeys.iter().map(|pubkey| pubkey.to_string()).collect();
+        let fetched = self
+            .fetch_mainnet_accounts(
+                &self.upstream(info)?,
+                &requested,
+                &[],
+                &FetchLimits {
+                    max_depth: Some(0),
+                    ..FetchLimits::default()
+                },
+                FetchOptions {
+                    fresh: true,
+                    ..FetchOptions::for_fork(info)
+                },
+            )
+            .await?;
+
+        let failed: HashSet<&str> = fetched
+            .failed
+            .iter()
+            .map(|failure| failure.pubkey.as_str())
+            .collect();
+        let mut latest: HashMap<Pubkey, Account> = fetched.accounts.iter().cloned().collect();
+        let mut updates = Vec::new();
+        let mut changed = Vec::new();
+        let mut unchanged = Vec::new();
+        let mut skipped = Vec::new();
+        let mut missing = Vec::new();
+        let mut conflicts = Vec::new();
+        for pubkey in pubkeys {
+            if failed.contains(pubkey.to_string().as_str()) {
+                continue;
+            }
+            let account = latest.remove(&pubkey);
+            if account.is_none() {
+                missing.push(pubkey.to_string());
+                // Accounts the simulator created itself, such as liteSVM's airdrop account,
+                // aren't upstream either. Only listed accounts are removed.
+                if untouched {
+                    continue;
+                }
+            }
+            if fork.svm.get_account(&pubkey) == account {
+                unchanged.push(pubkey.to_string());
+                continue;
+            }
+            if fork.is_modified(&pubkey) {
+                match policy {
+                    RefreshPolicy::Overwrite => {}
+                    RefreshPolicy::SkipModified => {
+                        skipped.push(pubkey.to_string());
+                        continue;
+                    }
+                    RefreshPolicy::FailOnConflict => {
+                        conflicts.push(pubkey.to_string());
+                        continue;
+                    }
+                }
+            }
+            changed.push(pubkey.to_string());
+            updates.push((pubkey, account));
+        }
+
+        if !conflicts.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Accounts modified locally differ from upstream: {}",
+                conflicts.join(", ")
+            ));
+        }
+        fork.refresh_upstream_accounts(updates)?;
+
+        info!(
+            "Refreshed {} account(s) from upstream: {} changed, {} skipped",
+            requested.len(),
+            changed.len(),
+            skipped.len()
+        );
+
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
+        Ok(json!({
+            "context": {"slot": clock.slot},
+            "value": {
+                "sourceSlot": fetched.slot,
+                "changed": changed,
+                "unchanged": unchanged,
+                "skipped": skipped,
+                "missing": missing,
+                "failed": fetched.failed
+            }
+        }))
+    }
+
+    /// Create a funded durable nonce account for the given authority
+    fn rpc_create_nonce_account(
+        &self,
+        fork: &mut Fork,
+        params: &Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let params_array = params
+            .as_ref()
+            .and_then(|p| p.as_array())
+            .filter(|p| !p.is_empty() && p.len() <= 2)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid params: expected 1 param (authority) or 2 params (authority, options)"
+                )
+            })?;
+
+        let authority: Pubkey = params_array[0]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid authority pubkey"))?
+            .parse()?;
+
+        let rent_exempt = fork
+            .svm
+            .minimum_balance_for_rent_exemption(NonceState::size());
+        let lamports = match params_array.get(1).map(|options| &options["lamports"]) {
+            Some(serde_json::Value::Null) | None => rent_exempt,
+            Some(value) => value
+                .as_u64()
+                .filter(|lamports| *lamports >= rent_exempt)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "lamports must be at least the rent-exempt minimum {}",
+                        rent_exempt
+                    )
+                })?,
+        };
+
+        // Not the durable nonce of the current blockhash, so the account can be used right away
+        // as if it had been created in an earlier block
+        let nonce_pubkey = Keypair::new().pubkey();
+        let durable_nonce = DurableNonce::from_blockhash(&hash::hashv(&[
+            nonce_pubkey.as_ref(),
+            fork.latest_blockhash().as_ref(),
+        ]));
+        let state = NonceState::new_initialized(&authority, durable_nonce, 5000);
+
+        fork.set_account(
+            nonce_pubkey,
+            Account {
+                lamports,
+                data: bincode::serialize(&NonceVersions::new(state))?,
+                owner: Pubkey::default(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        )?;
+
+        info!(
+            "Created nonce account {} for authority {}",
+            nonce_pubkey, authority
+        );
+
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
+        Ok(json!({
+            "context": {"slot": clock.slot},
+            "value": {
+                "nonceAccount": nonce_pubkey.to_string(),
+                "authority": authority.to_string(),
+                "nonce": durable_nonce.as_hash().to_string(),
+                "lamports": lamports
+            }
+        }))
+    }
+
+    /// Overwrite the price stored in a Pyth or Switchboard oracle account
+    fn rpc_set_oracle_price(
+        &self,
+        fork: &mut Fork,
+        params: &Option<serde_json::Value>,
+    ) -> Result<serde_json::Value> {
+        let params_array = params
+            .as_ref()
+            .and_then(|p| p.as_array())
+            .filter(|p| p.len() == 2)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Invalid params: expected 2 params (pubkey, priceParams)")
+            })?;
+
+        let pubkey: Pubkey = params_array[0]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid pubkey"))?
+            .parse()?;
+
+        let price_params: OraclePriceParams = serde_json::from_value(params_array[1].clone())
+            .map_err(|e| anyhow::anyhow!("Failed to parse price params: {}", e))?;
+
+        let mut account = fork
+            .svm
+            .get_account(&pubkey)
+            .ok_or_else(|| anyhow::anyhow!("Account {} not found in fork", pubkey))?;
+
+        let clock: Clock = fork.svm.get_sysvar::<Clock>();
+        let price = PriceOverride {
+            price: price_params.price,
+            conf: price_params.conf,
+            exponent: price_params.exponent,
+            publish_time: price_params.publish_time.unwrap_or(clock.unix_timestamp),
+            slot: clock.slot,
+        };
+
+        let kind = oracle::apply_price_override(&mut account.data, &price)?;
+        fork.set_account(pubkey, account)?;
+
+        info!(
+            "Set {} oracle {} price to {}e{}",
+            kind.as_str(),
+            pubkey,
+            price.price,
+            price.exponent
+        );
+
+        Ok(json!({
+            "context": {"slot": clock.slot},
+            "value": {
+                "oracle": kind.as_str(),
+                "publishTime": price.publish_time
+            }
+        }))
+    }
 }
 
 // curl -X POST http://localhost:8080/rpc/c6193d87-8e44-4a09-bb61-848dc54dc1dc \
@@ -590,3 +3369,287 @@ impl ForkManager {
 //     "method": "getBalance",
 //     "params": ["9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM"]
 //   }'
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::{AccountCache, CacheConfig},
+        storage::MemoryStorage,
+        upstream::UpstreamConfig,
+    };
+    use axum::{extract::State, routing::post, Json, Router};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    /// `getMultipleAccounts` upstream that records how many requests it served at once
+    #[derive(Default)]
+    struct MockUpstream {
+        requests: AtomicUsize,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+        /// Slot responses are served at, or the request's `minContextSlot` if that is later
+        slot: AtomicU64,
+        /// Config of every request
+        configs: std::sync::Mutex<Vec<serde_json::Value>>,
+        /// Accounts served with data that can't be decoded
+        undecodable: std::sync::Mutex<HashSet<Pubkey>>,
+    }
+
+    /// Lamports the mock reports for an account, so results can be matched to their pubkey
+    fn lamports(pubkey: &Pubkey) -> u64 {
+        u64::from_le_bytes(pubkey.to_bytes()[..8].try_into().unwrap())
+    }
+
+    async fn get_multiple_accounts(
+        State(mock): State<Arc<MockUpstream>>,
+        Json(request): Json<serde_json::Value>,
+    ) -> Json<serde_json::Value> {
+        let request_index = mock.requests.fetch_add(1, Ordering::SeqCst);
+        mock.configs
+            .lock()
+            .unwrap()
+            .push(request["params"][1].clone());
+        let in_flight = mock.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        mock.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        // The first chunk is answered last, so responses arrive out of order
+        let delay = if request_index == 0 { 200 } else { 30 };
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+        mock.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let pubkeys: Vec<Pubkey> = request["params"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|pubkey| pubkey.as_str().unwrap().parse().unwrap())
+            .collect();
+        let undecodable = mock.undecodable.lock().unwrap().clone();
+        let slot = mock.slot.load(Ordering::SeqCst).max(
+            request["params"][1]["minContextSlot"]
+                .as_u64()
+                .unwrap_or_default(),
+        );
+        let value: Vec<_> = pubkeys
+            .iter()
+            .map(|pubkey| {
+                let data = match undecodable.contains(pubkey) {
+                    true => json!(["not base64!", "base64"]),
+                    false => json!(["", "base64"]),
+                };
+                json!({
+                    "lamports": lamports(pubkey),
+                    "owner": Pubkey::default().to_string(),
+                    "data": data,
+                    "executable": false,
+                    "rentEpoch": 0
+                })
+            })
+            .collect();
+        Json(json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": {"context": {"slot": slot}, "value": value}
+        }))
+    }
+
+    /// A fork manager whose default cluster is a fresh mock upstream
+    async fn mock_manager(fetch_concurrency: usize) -> (Arc<MockUpstream>, ForkManager) {
+        let mock = Arc::new(MockUpstream {
+            slot: AtomicU64::new(1000),
+            ..Default::default()
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/", post(get_multiple_accounts))
+            .with_state(mock.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let upstreams = Upstreams::single(url, UpstreamConfig::default()).unwrap();
+        let manager = ForkManager::new(
+            Arc::new(MemoryStorage::new()),
+            "http://localhost".to_string(),
+            upstreams,
+        )
+        .with_fetch_concurrency(fetch_concurrency);
+        (mock, manager)
+    }
+
+    #[tokio::test]
+    async fn fetch_accounts_chunks_requests_concurrently_in_order() {
+        let (mock, manager) = mock_manager(3).await;
+
+        let pubkeys: Vec<Pubkey> = (0..1050).map(|_| Pubkey::new_unique()).collect();
+        let requested: Vec<String> = pubkeys.iter().map(Pubkey::to_string).collect();
+        let (accounts, slot) = manager.fetch_accounts(None, &requested).await.unwrap();
+
+        assert_eq!(slot, Some(1000));
+        assert_eq!(mock.requests.load(Ordering::SeqCst), 11);
+        let max_in_flight = mock.max_in_flight.load(Ordering::SeqCst);
+        assert!(
+            (2..=3).contains(&max_in_flight),
+            "{} requests in flight at once",
+            max_in_flight
+        );
+
+        // Responses are matched to their chunk; accounts load in reverse request order
+        let fetched: Vec<Pubkey> = accounts.iter().rev().map(|(pubkey, _)| *pubkey).collect();
+        assert_eq!(fetched, pubkeys);
+        for (pubkey, account) in &accounts {
+            assert_eq!(account.lamports, lamports(pubkey));
+        }
+    }
+
+    #[tokio::test]
+    async fn fetch_absent_accounts_uses_fork_options_and_reports_decode_failures() {
+        let (mock, manager) = mock_manager(1).await;
+        let (present, undecodable) = (Pubkey::new_unique(), Pubkey::new_unique());
+        mock.undecodable.lock().unwrap().insert(undecodable);
+
+        let mut info = ForkInfo::new(ForkId::new(), "http://localhost", 0, 15);
+        info.commitment = Commitment::Processed;
+        info.source_slot = Some(900);
+        let fork = Fork::new(LiteSVM::new(), BlockProduction::default()).unwrap();
+        let mut fork = fork.lock().await;
+
+        let error = manager
+            .fetch_absent_accounts(&mut fork, &info, [present, undecodable])
+            .await
+            .unwrap_err();
+        assert!(
+            error.to_string().contains(&undecodable.to_string()),
+            "{}",
+            error
+        );
+        assert!(!fork.missing_accounts.contains(&undecodable));
+        let config = mock.configs.lock().unwrap()[0].clone();
+        assert_eq!(config["commitment"], "processed");
+        assert_eq!(config["minContextSlot"], 900);
+
+        // The account is fetched once upstream serves it properly
+        mock.undecodable.lock().unwrap().clear();
+        manager
+            .fetch_absent_accounts(&mut fork, &info, [present, undecodable])
+            .await
+            .unwrap();
+        for pubkey in [present, undecodable] {
+            assert_eq!(
+                fork.svm.get_account(&pubkey).unwrap().lamports,
+                lamports(&pubkey)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cached_accounts_agree_with_fresh_batches_on_a_slot() {
+        let (mock, manager) = mock_manager(1).await;
+        let manager = manager.with_cache(AccountCache::new(CacheConfig {
+            max_age: Duration::from_secs(600),
+            ..Default::default()
+        }));
+        let upstream = manager
+            .upstreams
+            .cluster(manager.upstreams.default_cluster())
+            .unwrap();
+        let program = |program_id, slot| {
+            let account = Account {
+                lamports: 1,
+                owner: dependencies::LOADERS[0],
+                executable: true,
+                ..Default::default()
+            };
+            manager
+                .cache
+                .insert(upstream.id(), program_id, &account, slot);
+        };
+        let fetch = |requested: Vec<Pubkey>| {
+            let requested: Vec<String> = requested.iter().map(Pubkey::to_string).collect();
+            let manager = &manager;
+            async move { manager.fetch_accounts(None, &requested).await.unwrap() }
+        };
+        let lamports_of = |accounts: &[(Pubkey, Account)], pubkey| {
+            accounts
+                .iter()
+                .find(|(key, _)| *key == pubkey)
+                .map(|(_, account)| account.lamports)
+        };
+        let pinned = || {
+            mock.configs
+                .lock()
+                .unwrap()
+                .drain(..)
+                .filter_map(|config| config["minContextSlot"].as_u64())
+                .collect::<Vec<_>>()
+        };
+
+        // Other accounts cached before upstream's slot are fetched again at that slot
+        let (stale, fresh) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let cached = Account {
+            lamports: 1,
+            ..Default::default()
+        };
+        manager.cache.insert(upstream.id(), stale, &cached, 900);
+        let (accounts, slot) = fetch(vec![stale, fresh]).await;
+        assert_eq!(slot, Some(1000));
+        assert_eq!(lamports_of(&accounts, stale), Some(lamports(&stale)));
+        assert_eq!(pinned(), vec![1000]);
+
+        // Cached programs older than upstream's slot are served without a refetch
+        let (program_id, fresh) = (Pubkey::new_unique(), Pubkey::new_unique());
+        program(program_id, 900);
+        let (accounts, slot) = fetch(vec![program_id, fresh]).await;
+        assert_eq!(slot, Some(1000));
+        assert_eq!(lamports_of(&accounts, program_id), Some(1));
+        assert_eq!(lamports_of(&accounts, fresh), Some(lamports(&fresh)));
+        assert!(pinned().is_empty());
+
+        // Newer ones are served too, and the result is never older than them
+        let (program_id, fresh) = (Pubkey::new_unique(), Pubkey::new_unique());
+        program(program_id, 1100);
+        let (accounts, slot) = fetch(vec![program_id, fresh]).await;
+        assert_eq!(slot, Some(1100));
+        assert_eq!(lamports_of(&accounts, program_id), Some(1));
+        assert_eq!(lamports_of(&accounts, fresh), Some(lamports(&fresh)));
+        let pinned = pinned();
+        assert!(!pinned.is_empty() && pinned.iter().all(|slot| *slot == 1100));
+    }
+
+    #[tokio::test]
+    async fn expired_fork_is_evicted_with_one_event() {
+        let (_mock, manager) = mock_manager(1).await;
+        let manager = Arc::new(manager);
+        let mut events = manager.subscribe();
+
+        let mut info = ForkInfo::new(ForkId::new(), "http://localhost", 0, 15);
+        info.expires_at = chrono::Utc::now() + chrono::Duration::milliseconds(100);
+        let fork = Fork::new(LiteSVM::new(), BlockProduction::default()).unwrap();
+        manager.storage.save_fork(&info).await.unwrap();
+        manager
+            .forks
+            .write()
+            .await
+            .insert(info.fork_id.clone(), fork);
+
+        let reaper = manager.spawn_reaper(Duration::from_millis(50));
+        let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("no event for the expired fork")
+            .unwrap();
+        let ForkEvent::Expired {
+            fork_id,
+            expired_at,
+        } = event;
+        assert_eq!(fork_id, info.fork_id);
+        assert_eq!(expired_at, info.expires_at);
+
+        // Neither later reaper passes nor requests for the fork publish it again
+        let error = manager.touch_fork(&info.fork_id).await.unwrap_err();
+        assert!(error.is::<ForkExpired>());
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        reaper.abort();
+        assert!(matches!(
+            events.try_recv(),
+            Err(broadcast::error::TryRecvError::Empty)
+        ));
+        assert!(manager.forks.read().await.is_empty());
+    }
+}
@@ -1,7 +1,14 @@
 pub mod api;
+pub mod archive;
+pub mod cache;
+pub mod dependencies;
+pub mod diff;
+pub mod fixtures;
 pub mod fork;
+pub mod oracle;
 pub mod storage;
 pub mod types;
+pub mod upstream;
 
-pub use storage::Storage;
+pub use storage::{FileStorage, MemoryStorage, Storage};
 pub use types::*;
@@ -1,26 +1,197 @@
 use anyhow::Result;
 use axum::{
+    extract::DefaultBodyLimit,
     routing::{delete, get, post},
     Router,
 };
-use clap::Parser;
-use sol_sim::{api, fork::ForkManager, storage::Storage};
+use clap::{Parser, Subcommand};
+use sol_sim::{
+    api,
+    cache::{AccountCache, CacheConfig},
+    fixtures,
+    fork::{ForkManager, TtlConfig, DEFAULT_FETCH_CONCURRENCY},
+    storage::{FileStorage, MemoryStorage, Storage},
+    upstream::{ClusterConfig, UpstreamConfig, Upstreams, UpstreamsConfig, FIXTURES_CLUSTER},
+    DEFAULT_TTL_MINUTES,
+};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Largest fork archive accepted by `POST /forks/import`
+const MAX_ARCHIVE_BYTES: usize = 512 * 1024 * 1024;
+
 #[derive(Parser)]
 #[command(name = "sol-sim")]
 #[command(about = "Solana Fork Simulation Engine - MVP")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Port to listen on
     #[arg(long, default_value = "8080")]
     port: u16,
 
     /// Solana RPC URL (mainnet/testnet/devnet)
-    #[arg(long, default_value = "https://api.mainnet-beta.solana.com")]
+    #[arg(
+        long,
+        global = true,
+        default_value = "https://api.mainnet-beta.solana.com"
+    )]
     solana_rpc: String,
+
+    /// JSON file of named upstream clusters with endpoints and headers; replaces --solana-rpc
+    #[arg(long, global = true)]
+    upstreams: Option<PathBuf>,
+
+    /// Directory of account files (`solana account --output json`) and `<program_id>.so`
+    /// programs, served as the `fixtures` cluster without any network access
+    #[arg(long, global = true)]
+    fixtures: Option<PathBuf>,
+
+    /// Host a fork's `rpcUrl` may point at, repeatable; `*` allows any. Without one, forks
+    /// can only use the configured clusters.
+    #[arg(long = "rpc-url-host", global = true)]
+    rpc_url_hosts: Vec<String>,
+
+    /// Cluster used by forks that don't name one
+    #[arg(long, global = true)]
+    default_cluster: Option<String>,
+
+    /// Directory for durable fork storage. Forks are kept in memory only if omitted.
+    #[arg(long)]
+    data_dir: Option<PathBuf>,
+
+    /// Maximum concurrent `getMultipleAccounts` requests per account fetch
+    #[arg(long, global = true, default_value_t = DEFAULT_FETCH_CONCURRENCY)]
+    fetch_concurrency: usize,
+
+    /// Timeout for a single upstream request, in milliseconds
+    #[arg(long, global = true, default_value_t = 30_000)]
+    upstream_timeout_ms: u64,
+
+    /// Retries for failed upstream requests (timeouts, HTTP 429/5xx, transient RPC errors)
+    #[arg(long, global = true, default_value_t = 3)]
+    upstream_retries: u32,
+
+    /// Seconds fetched accounts are served from the shared cache (0 disables)
+    #[arg(long, default_value_t = 0)]
+    cache_max_age_secs: u64,
+
+    /// Seconds program and program data accounts are served from the shared cache (0 disables)
+    #[arg(long, default_value_t = 600)]
+    cache_program_max_age_secs: u64,
+
+    /// Maximum size of the shared account cache in MiB
+    #[arg(long, default_value_t = 256)]
+    cache_max_mb: usize,
+
+    /// Minutes a fork lives after creation or its last request, unless it sets `ttlMinutes`
+    #[arg(long, default_value_t = DEFAULT_TTL_MINUTES)]
+    default_ttl_minutes: i64,
+
+    /// Longest `ttlMinutes` a fork may request
+    #[arg(long, default_value_t = 24 * 60)]
+    max_ttl_minutes: i64,
+
+    /// Seconds between sweeps that evict expired forks
+    #[arg(long, default_value_t = 30)]
+    reap_interval_secs: u64,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch accounts and their dependencies into a fixture directory for --fixtures
+    Dump {
+        /// Directory to write the account files to
+        #[arg(long)]
+        out: PathBuf,
+
+        /// Cluster to fetch from; the default cluster if omitted
+        #[arg(long)]
+        cluster: Option<String>,
+
+        /// Accounts to fetch
+        #[arg(required = true)]
+        accounts: Vec<String>,
+    },
+}
+
+/// Build the upstream clusters from `--upstreams` or `--solana-rpc`, plus `--fixtures`
+fn upstreams(args: &Args) -> Result<Upstreams> {
+    let config = UpstreamConfig {
+        timeout: Duration::from_millis(args.upstream_timeout_ms),
+        max_retries: args.upstream_retries,
+        ..UpstreamConfig::default()
+    };
+    let mut upstreams = match &args.upstreams {
+        Some(path) => UpstreamsConfig::load(path)?,
+        None => {
+            info!("Solana RPC: {}", args.solana_rpc);
+            UpstreamsConfig::single(args.solana_rpc.clone())
+        }
+    };
+    if let Some(dir) = &args.fixtures {
+        upstreams.clusters.insert(
+            FIXTURES_CLUSTER.to_string(),
+            ClusterConfig::fixtures(dir.clone()),
+        );
+    }
+    if let Some(default) = &args.default_cluster {
+        upstreams.default = Some(default.clone());
+    }
+    upstreams
+        .rpc_url_hosts
+        .extend(args.rpc_url_hosts.iter().cloned());
+
+    let upstreams = Upstreams::new(upstreams, config)?;
+    for (name, source) in upstreams.clusters() {
+        info!("Upstream cluster {}: {}", name, source);
+    }
+    info!("Default cluster: {}", upstreams.default_cluster());
+    match upstreams.rpc_url_hosts() {
+        [] => info!("rpcUrl disabled; allow hosts with --rpc-url-host"),
+        hosts => info!("rpcUrl hosts: {}", hosts.join(", ")),
+    }
+    Ok(upstreams)
+}
+
+/// Write `accounts` and their dependencies to `out` as fixtures
+async fn dump(
+    args: &Args,
+    out: &std::path::Path,
+    cluster: Option<&str>,
+    accounts: &[String],
+) -> Result<()> {
+    let manager = ForkManager::new(
+        Arc::new(MemoryStorage::new()),
+        String::new(),
+        upstreams(args)?,
+    )
+    .with_fetch_concurrency(args.fetch_concurrency);
+    // SlotHashes lets forks created from the fixtures sync their chain context
+    let mut pubkeys = accounts.to_vec();
+    pubkeys.push(solana_sysvar::slot_hashes::ID.to_string());
+    let (accounts, slot) = manager.fetch_accounts(cluster, &pubkeys).await?;
+    let block_time = match slot {
+        Some(slot) => manager
+            .block_time(cluster, slot)
+            .await
+            .inspect_err(|e| warn!("Not recording a block time: {}", e))
+            .ok(),
+        None => None,
+    };
+    fixtures::write(out, &accounts, slot, block_time)?;
+    info!(
+        "Wrote {} account(s) at slot {} to {}",
+        accounts.len(),
+        slot.unwrap_or_default(),
+        out.display()
+    );
+    Ok(())
 }
 
 #[tokio::main]
@@ -35,32 +206,102 @@ async fn main() -> Result<()> {
         .init();
 
     let args = Args::parse();
+    if let Some(Command::Dump {
+        out,
+        cluster,
+        accounts,
+    }) = &args.command
+    {
+        return dump(&args, out, cluster.as_deref(), accounts).await;
+    }
 
     // Initialize start time for uptime tracking
     api::init_start_time();
 
     info!("Starting Solana Fork Simulation Engine");
     info!("Port: {}", args.port);
-    info!("Solana RPC: {}", args.solana_rpc);
+    info!("Fetch concurrency: {}", args.fetch_concurrency);
+    info!(
+        "Upstream timeout: {}ms, retries: {}",
+        args.upstream_timeout_ms, args.upstream_retries
+    );
+
+    // Initialize storage
+    let storage: Arc<dyn Storage> = match &args.data_dir {
+        Some(data_dir) => {
+            info!("Storage: {}", data_dir.display());
+            Arc::new(FileStorage::open(data_dir).await?)
+        }
+        None => {
+            info!("Storage: in-memory");
+            Arc::new(MemoryStorage::new())
+        }
+    };
 
-    // Initialize in-memory storage
-    let storage = Storage::new();
+    // Initialize upstreams
+    let upstreams = upstreams(&args)?;
+
+    let cache_config = CacheConfig {
+        max_age: Duration::from_secs(args.cache_max_age_secs),
+        program_max_age: Duration::from_secs(args.cache_program_max_age_secs),
+        max_bytes: args.cache_max_mb * 1024 * 1024,
+    };
+    info!(
+        "Account cache: max age {}s, programs {}s, {} MiB",
+        args.cache_max_age_secs, args.cache_program_max_age_secs, args.cache_max_mb
+    );
+
+    if args.default_ttl_minutes < 1 || args.default_ttl_minutes > args.max_ttl_minutes {
+        return Err(anyhow::anyhow!(
+            "--default-ttl-minutes must be between 1 and --max-ttl-minutes ({})",
+            args.max_ttl_minutes
+        ));
+    }
+    info!(
+        "Fork TTL: {} minutes, at most {}",
+        args.default_ttl_minutes, args.max_ttl_minutes
+    );
 
     // Initialize fork manager
-    let manager = Arc::new(ForkManager::new(
-        storage,
-        format!("http://127.0.0.1:{}", args.port),
-        args.solana_rpc,
-    ));
+    let manager = Arc::new(
+        ForkManager::new(
+            storage,
+            format!("http://127.0.0.1:{}", args.port),
+            upstreams,
+        )
+        .with_fetch_concurrency(args.fetch_concurrency)
+        .with_cache(AccountCache::new(cache_config))
+        .with_ttl(TtlConfig {
+            default_minutes: args.default_ttl_minutes,
+            max_minutes: args.max_ttl_minutes,
+        }),
+    );
+    manager.spawn_reaper(Duration::from_secs(args.reap_interval_secs.max(1)));
+    info!(
+        "Evicting expired forks every {}s",
+        args.reap_interval_secs.max(1)
+    );
 
     // Build router
     let app = Router::new()
         .route("/health", get(api::health))
+        .route("/cache/stats", get(api::cache_stats))
         // Fork management endpoints
         .route("/rpc/{fork_id}", post(api::handle_rpc))
         .route("/forks", post(api::create_fork))
+        .route("/forks/plan", post(api::plan_fork))
         .route("/forks/{fork_id}", get(api::get_fork))
         .route("/forks/{fork_id}", delete(api::delete_fork))
+        .route("/forks/{fork_id}/clone", post(api::clone_fork))
+        .route("/forks/{fork_id}/extend", post(api::extend_fork))
+        .route("/forks/{fork_id}/export", get(api::export_fork))
+        .route("/forks/{fork_id}/reset", post(api::reset_fork))
+        .route("/forks/{fork_id}/diff", get(api::diff_fork))
+        .route("/forks/{fork_id}/patch", post(api::patch_fork))
+        .route(
+            "/forks/import",
+            post(api::import_fork).layer(DefaultBodyLimit::max(MAX_ARCHIVE_BYTES)),
+        )
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
         .with_state(manager);
@@ -72,9 +313,18 @@ async fn main() -> Result<()> {
     info!("Server listening on {}", addr);
     info!("API documentation:");
     info!("  POST   /forks              - Create new fork");
+    info!("  POST   /forks/plan         - Preview the accounts a fork would load");
     info!("  GET    /forks/:id          - Get fork info");
     info!("  DELETE /forks/:id          - Delete fork");
+    info!("  POST   /forks/:id/clone    - Clone fork");
+    info!("  POST   /forks/:id/extend   - Extend fork TTL or pin it");
+    info!("  GET    /forks/:id/export   - Export fork archive");
+    info!("  POST   /forks/import       - Import fork archive");
+    info!("  POST   /forks/:id/reset    - Reset fork to creation state");
+    info!("  GET    /forks/:id/diff     - Diff fork against creation state");
+    info!("  POST   /forks/:id/patch    - Apply diff to fork");
     info!("  POST   /rpc/:id            - Send JSON-RPC request");
+    info!("  GET    /cache/stats        - Upstream account cache statistics");
 
     axum::serve(listener, app).await?;
 
@@ -0,0 +1,377 @@
+use anyhow::Result;
+
+/// Anchor discriminator of the Pyth pull oracle `PriceUpdateV2` account
+const PYTH_PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// Magic number at the start of every legacy Pyth account
+const PYTH_LEGACY_MAGIC: u32 = 0xa1b2c3d4;
+/// Legacy Pyth account type for price accounts
+const PYTH_LEGACY_PRICE_TYPE: u32 = 3;
+
+/// Anchor discriminator of the Switchboard on-demand `PullFeedAccountData` account
+const SWITCHBOARD_PULL_FEED_DISCRIMINATOR: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+/// Switchboard on-demand values are fixed point with 18 decimals
+const SWITCHBOARD_PRECISION: i32 = 18;
+
+/// Price values written into an oracle account
+#[derive(Debug, Clone, Copy)]
+pub struct PriceOverride {
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub slot: u64,
+}
+
+/// Oracle account layouts that can be rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OracleKind {
+    PythPriceUpdateV2,
+    PythLegacyPrice,
+    SwitchboardPullFeed,
+}
+
+impl OracleKind {
+    /// Detect the oracle layout from the account data
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&PYTH_PRICE_UPDATE_V2_DISCRIMINATOR) {
+            Some(Self::PythPriceUpdateV2)
+        } else if data.starts_with(&SWITCHBOARD_PULL_FEED_DISCRIMINATOR) {
+            Some(Self::SwitchboardPullFeed)
+        } else if data.len() >= 12
+            && read_u32(data, 0) == PYTH_LEGACY_MAGIC
+            && read_u32(data, 8) == PYTH_LEGACY_PRICE_TYPE
+        {
+            Some(Self::PythLegacyPrice)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PythPriceUpdateV2 => "pythPriceUpdateV2",
+            Self::PythLegacyPrice => "pythLegacyPrice",
+            Self::SwitchboardPullFeed => "switchboardPullFeed",
+        }
+    }
+}
+
+/// Rewrite the price stored in an oracle account in place
+pub fn apply_price_override(data: &mut [u8], price: &PriceOverride) -> Result<OracleKind> {
+    let kind = OracleKind::detect(data)
+        .ok_or_else(|| anyhow::anyhow!("Account is not a supported Pyth or Switchboard oracle"))?;
+
+    match kind {
+        OracleKind::PythPriceUpdateV2 => write_pyth_price_update_v2(data, price)?,
+        OracleKind::PythLegacyPrice => write_pyth_legacy_price(data, price)?,
+        OracleKind::SwitchboardPullFeed => write_switchboard_pull_feed(data, price)?,
+    }
+
+    Ok(kind)
+}
+
+/// PriceUpdateV2 layout (borsh):
+/// - Bytes 0-7: discriminator
+/// - Bytes 8-39: write authority
+/// - Byte 40: verification level tag (0 = Partial { num_signatures: u8 }, 1 = Full)
+/// - PriceFeedMessage: feed_id [u8; 32], price i64, conf u64, exponent i32,
+///   publish_time i64, prev_publish_time i64, ema_price i64, ema_conf u64
+/// - posted_slot u64
+fn write_pyth_price_update_v2(data: &mut [u8], price: &PriceOverride) -> Result<()> {
+    let message = match data.get(40) {
+        Some(0) => 42,
+        Some(1) => 41,
+        _ => return Err(anyhow::anyhow!("Invalid PriceUpdateV2 verification level")),
+    };
+    ensure_len(data, message + 84 + 8, "PriceUpdateV2")?;
+
+    let price_offset = message + 32;
+    let previous_publish_time = read_i64(data, price_offset + 20);
+    write_i64(data, price_offset, price.price);
+    write_u64(data, price_offset + 8, price.conf);
+    write_i32(data, price_offset + 16, price.exponent);
+    write_i64(data, price_offset + 20, price.publish_time);
+    write_i64(data, price_offset + 28, previous_publish_time);
+    write_i64(data, price_offset + 36, price.price);
+    write_u64(data, price_offset + 44, price.conf);
+    write_u64(data, message + 84, price.slot);
+    Ok(())
+}
+
+/// Legacy Pyth price account layout (repr C):
+/// - expo i32 @20, last_slot u64 @32, valid_slot u64 @40
+/// - ema_price.val i64 @48, ema_conf.val i64 @72, timestamp i64 @96
+/// - prev_slot u64 @176, prev_price i64 @184, prev_conf u64 @192, prev_timestamp i64 @200
+/// - agg: price i64 @208, conf u64 @216, status u32 @224, corp_act u32 @228, pub_slot u64 @232
+fn write_pyth_legacy_price(data: &mut [u8], price: &PriceOverride) -> Result<()> {
+    ensure_len(data, 240, "legacy Pyth price")?;
+
+    let (prev_price, prev_conf) = (read_i64(data, 208), read_u64(data, 216));
+    let (prev_slot, prev_timestamp) = (read_u64(data, 232), read_i64(data, 96));
+    write_u64(data, 176, prev_slot);
+    write_i64(data, 184, prev_price);
+    write_u64(data, 192, prev_conf);
+    write_i64(data, 200, prev_timestamp);
+
+    write_i32(data, 20, price.exponent);
+    write_u64(data, 32, price.slot);
+    write_u64(data, 40, price.slot);
+    write_i64(data, 48, price.price);
+    write_i64(data, 72, price.conf as i64);
+    write_i64(data, 96, price.publish_time);
+    write_i64(data, 208, price.price);
+    write_u64(data, 216, price.conf);
+    write_u32(data, 224, 1); // Trading
+    write_u64(data, 232, price.slot);
+    Ok(())
+}
+
+/// Switchboard on-demand PullFeedAccountData layout (zero-copy, after the 8-byte discriminator):
+/// - submissions [OracleSubmission; 32] @8: oracle Pubkey, slot u64, landed_at u64, value i128
+/// - last_update_timestamp i64 @2216
+/// - result @2264: value, std_dev, mean, range, min_value, max_value (i128 each),
+///   num_samples u8 @2360, slot u64 @2368, min_slot u64 @2376, max_slot u64 @2384
+fn write_switchboard_pull_feed(data: &mut [u8], price: &PriceOverride) -> Result<()> {
+    ensure_len(data, 2392, "Switchboard pull feed")?;
+
+    let value = scale_to_switchboard(price.price as i128, price.exponent)?;
+    let std_dev = scale_to_switchboard(price.conf as i128, price.exponent)?;
+
+    // Staleness checks read the individual submissions, so every populated submission is
+    // refreshed. A feed without any submissions gets a single one.
+    let mut samples = 0u8;
+    for i in 0..32 {
+        let offset = 8 + i * 64;
+        let populated = data[offset..offset + 32].iter().any(|b| *b != 0);
+        if populated || i == 0 {
+            write_u64(data, offset + 32, price.slot);
+            write_u64(data, offset + 40, price.slot);
+            write_i128(data, offset + 48, value);
+            samples += 1;
+        }
+    }
+
+    write_i64(data, 2216, price.publish_time);
+    write_i128(data, 2264, value);
+    write_i128(data, 2280, std_dev);
+    write_i128(data, 2296, value);
+    write_i128(data, 2312, 0);
+    write_i128(data, 2328, value);
+    write_i128(data, 2344, value);
+    data[2360] = samples;
+    write_u64(data, 2368, price.slot);
+    write_u64(data, 2376, price.slot);
+    write_u64(data, 2384, price.slot);
+    Ok(())
+}
+
+/// Convert `value * 10^exponent` into Switchboard's 18-decimal fixed point
+fn scale_to_switchboard(value: i128, exponent: i32) -> Result<i128> {
+    let shift = SWITCHBOARD_PRECISION + exponent;
+    let factor = 10i128
+        .checked_pow(shift.unsigned_abs())
+        .ok_or_else(|| anyhow::anyhow!("Exponent {} out of range", exponent))?;
+    if shift >= 0 {
+        value
+            .checked_mul(factor)
+            .ok_or_else(|| anyhow::anyhow!("Price overflows Switchboard precision"))
+    } else {
+        Ok(value / factor)
+    }
+}
+
+fn ensure_len(data: &[u8], len: usize, kind: &str) -> Result<()> {
+    if data.len() < len {
+        return Err(anyhow::anyhow!(
+            "{} account too small: expected at least {} bytes, got {}",
+            kind,
+            len,
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_i64(data: &[u8], offset: usize) -> i64 {
+    i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_i32(data: &mut [u8], offset: usize, value: i32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_i64(data: &mut [u8], offset: usize, value: i64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_i128(data: &mut [u8], offset: usize, value: i128) {
+    data[offset..offset + 16].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICE: PriceOverride = PriceOverride {
+        price: 150_000_000,
+        conf: 1_000_000,
+        exponent: -8,
+        publish_time: 1_700_000_000,
+        slot: 250_000_000,
+    };
+
+    fn i32_at(data: &[u8], offset: usize) -> i32 {
+        i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn i128_at(data: &[u8], offset: usize) -> i128 {
+        i128::from_le_bytes(data[offset..offset + 16].try_into().unwrap())
+    }
+
+    /// A `PriceUpdateV2` account as posted by the Pyth receiver, 134 bytes with a partial
+    /// verification level
+    fn pyth_price_update_v2() -> Vec<u8> {
+        let mut data = PYTH_PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend([7; 32]); // write_authority
+        data.extend([0, 5]); // VerificationLevel::Partial { num_signatures: 5 }
+        data.extend([9; 32]); // feed_id
+        data.extend(100i64.to_le_bytes()); // price
+        data.extend(2u64.to_le_bytes()); // conf
+        data.extend((-6i32).to_le_bytes()); // exponent
+        data.extend(1_000i64.to_le_bytes()); // publish_time
+        data.extend(900i64.to_le_bytes()); // prev_publish_time
+        data.extend(99i64.to_le_bytes()); // ema_price
+        data.extend(3u64.to_le_bytes()); // ema_conf
+        data.extend(50u64.to_le_bytes()); // posted_slot
+        assert_eq!(data.len(), 134);
+        data
+    }
+
+    #[test]
+    fn pyth_price_update_v2_offsets() {
+        let mut data = pyth_price_update_v2();
+        let kind = apply_price_override(&mut data, &PRICE).unwrap();
+        assert_eq!(kind, OracleKind::PythPriceUpdateV2);
+
+        assert_eq!(&data[8..40], &[7; 32]);
+        assert_eq!(&data[40..42], &[0, 5]);
+        assert_eq!(&data[42..74], &[9; 32]);
+        assert_eq!(read_i64(&data, 74), PRICE.price);
+        assert_eq!(read_u64(&data, 82), PRICE.conf);
+        assert_eq!(i32_at(&data, 90), PRICE.exponent);
+        assert_eq!(read_i64(&data, 94), PRICE.publish_time);
+        assert_eq!(read_i64(&data, 102), 1_000);
+        assert_eq!(read_i64(&data, 110), PRICE.price);
+        assert_eq!(read_u64(&data, 118), PRICE.conf);
+        assert_eq!(read_u64(&data, 126), PRICE.slot);
+    }
+
+    #[test]
+    fn pyth_price_update_v2_full_verification_offsets() {
+        // `Full` has no signature count, so the message starts a byte earlier
+        let mut data = pyth_price_update_v2();
+        data.remove(41);
+        data[40] = 1;
+        apply_price_override(&mut data, &PRICE).unwrap();
+
+        assert_eq!(&data[41..73], &[9; 32]);
+        assert_eq!(read_i64(&data, 73), PRICE.price);
+        assert_eq!(i32_at(&data, 89), PRICE.exponent);
+        assert_eq!(read_u64(&data, 125), PRICE.slot);
+    }
+
+    #[test]
+    fn pyth_legacy_price_offsets() {
+        // Price account of the legacy Pyth program (`PriceAccount`, 3312 bytes)
+        let mut data = vec![0; 3312];
+        data[0..4].copy_from_slice(&PYTH_LEGACY_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&2u32.to_le_bytes()); // version
+        data[8..12].copy_from_slice(&PYTH_LEGACY_PRICE_TYPE.to_le_bytes());
+        data[96..104].copy_from_slice(&1_000i64.to_le_bytes()); // timestamp
+        data[112..144].copy_from_slice(&[4; 32]); // product account
+        data[208..216].copy_from_slice(&100i64.to_le_bytes()); // agg.price
+        data[216..224].copy_from_slice(&2u64.to_le_bytes()); // agg.conf
+        data[232..240].copy_from_slice(&50u64.to_le_bytes()); // agg.pub_slot
+
+        let kind = apply_price_override(&mut data, &PRICE).unwrap();
+        assert_eq!(kind, OracleKind::PythLegacyPrice);
+
+        assert_eq!(i32_at(&data, 20), PRICE.exponent);
+        assert_eq!(read_u64(&data, 32), PRICE.slot); // last_slot
+        assert_eq!(read_u64(&data, 40), PRICE.slot); // valid_slot
+        assert_eq!(read_i64(&data, 48), PRICE.price); // ema_price.val
+        assert_eq!(read_i64(&data, 72), PRICE.conf as i64); // ema_conf.val
+        assert_eq!(read_i64(&data, 96), PRICE.publish_time);
+        assert_eq!(&data[112..144], &[4; 32]);
+        // The previous aggregate moves to the prev_* fields
+        assert_eq!(read_u64(&data, 176), 50);
+        assert_eq!(read_i64(&data, 184), 100);
+        assert_eq!(read_u64(&data, 192), 2);
+        assert_eq!(read_i64(&data, 200), 1_000);
+        assert_eq!(read_i64(&data, 208), PRICE.price);
+        assert_eq!(read_u64(&data, 216), PRICE.conf);
+        assert_eq!(read_u32(&data, 224), 1); // Trading
+        assert_eq!(read_u64(&data, 232), PRICE.slot);
+    }
+
+    #[test]
+    fn switchboard_pull_feed_offsets() {
+        // `PullFeedAccountData` of Switchboard on-demand, 3208 bytes
+        let mut data = vec![0; 3208];
+        data[..8].copy_from_slice(&SWITCHBOARD_PULL_FEED_DISCRIMINATOR);
+        // Submissions 0 and 2 come from oracles, submission 1 is empty
+        data[8..40].copy_from_slice(&[1; 32]);
+        data[136..168].copy_from_slice(&[2; 32]);
+
+        let kind = apply_price_override(&mut data, &PRICE).unwrap();
+        assert_eq!(kind, OracleKind::SwitchboardPullFeed);
+
+        // 1.5 and 0.01 with 18 decimals
+        let value = 1_500_000_000_000_000_000i128;
+        let std_dev = 10_000_000_000_000_000i128;
+        for submission in [8, 136] {
+            assert_eq!(read_u64(&data, submission + 32), PRICE.slot); // slot
+            assert_eq!(read_u64(&data, submission + 40), PRICE.slot); // landed_at
+            assert_eq!(i128_at(&data, submission + 48), value);
+        }
+        assert!(data[72..136].iter().all(|b| *b == 0));
+
+        assert_eq!(read_i64(&data, 2216), PRICE.publish_time);
+        assert_eq!(i128_at(&data, 2264), value);
+        assert_eq!(i128_at(&data, 2280), std_dev);
+        assert_eq!(i128_at(&data, 2296), value); // mean
+        assert_eq!(i128_at(&data, 2312), 0); // range
+        assert_eq!(i128_at(&data, 2328), value); // min_value
+        assert_eq!(i128_at(&data, 2344), value); // max_value
+        assert_eq!(data[2360], 2); // num_samples
+        assert_eq!(read_u64(&data, 2368), PRICE.slot);
+        assert_eq!(read_u64(&data, 2376), PRICE.slot);
+        assert_eq!(read_u64(&data, 2384), PRICE.slot);
+    }
+
+    #[test]
+    fn rejects_unknown_and_truncated_accounts() {
+        assert!(apply_price_override(&mut [0; 200], &PRICE).is_err());
+
+        let mut data = pyth_price_update_v2();
+        data.truncate(100);
+        assert!(apply_price_override(&mut data, &PRICE).is_err());
+    }
+}
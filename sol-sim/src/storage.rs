@@ -1,46 +1,602 @@
-use crate::{ForkId, ForkInfo};
+use crate::{
+    archive::{ArchiveManifest, ForkArchive},
+    ForkId, ForkInfo, TransactionRecord,
+};
 use anyhow::Result;
+use async_trait::async_trait;
+use solana_sdk::{account::Account, pubkey::Pubkey};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
+use tracing::{info, warn};
 
+/// Fork metadata and state storage
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn save_fork(&self, fork: &ForkInfo) -> Result<()>;
+
+    async fn get_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>>;
+
+    /// Metadata of every stored fork
+    async fn list_forks(&self) -> Result<Vec<ForkInfo>>;
+
+    /// Refresh TTL with `ForkInfo::refresh`. Returns updated ForkInfo if present.
+    async fn refresh_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>>;
+
+    async fn delete_fork(&self, fork_id: &ForkId) -> Result<()>;
+
+    /// Delete a fork's saved state, keeping its metadata
+    async fn delete_fork_state(&self, _fork_id: &ForkId) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether fork state survives a restart. The state methods are only called when this is true.
+    fn persists_state(&self) -> bool {
+        false
+    }
+
+    /// Save fork state. `accounts` lists changed accounts, where `None` means the account was
+    /// removed, and `transactions` the transactions executed since the last save; the
+    /// manifest's own transaction list is left empty. With `replace` the stored accounts and
+    /// transaction history are replaced by `accounts` and `transactions` entirely.
+    async fn save_fork_state(
+        &self,
+        _fork_id: &ForkId,
+        _manifest: &ArchiveManifest,
+        _accounts: &[(Pubkey, Option<Account>)],
+        _transactions: &[TransactionRecord],
+        _replace: bool,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Load the saved state of a fork. Returns `None` if no state was saved.
+    async fn load_fork_state(&self, _fork_id: &ForkId) -> Result<Option<ForkArchive>> {
+        Ok(None)
+    }
+
+    /// Save the state a fork was created with
+    async fn save_fork_origin(&self, _fork_id: &ForkId, _origin: &ForkArchive) -> Result<()> {
+        Ok(())
+    }
+
+    async fn load_fork_origin(&self, _fork_id: &ForkId) -> Result<Option<ForkArchive>> {
+        Ok(None)
+    }
+}
+
+/// Transaction history of a fork, one JSON record per line
+const TRANSACTIONS_FILE: &str = "transactions.jsonl";
+
+/// Keeps fork metadata in memory; forks are lost on restart
 #[derive(Clone)]
-pub struct Storage {
+pub struct MemoryStorage {
     forks: Arc<RwLock<HashMap<ForkId, ForkInfo>>>,
 }
 
-impl Storage {
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStorage {
     pub fn new() -> Self {
         Self {
             forks: Arc::new(RwLock::new(HashMap::new())),
         }
     }
+}
 
-    pub async fn save_fork(&self, fork: &ForkInfo) -> Result<()> {
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn save_fork(&self, fork: &ForkInfo) -> Result<()> {
         let mut forks = self.forks.write().await;
         forks.insert(fork.fork_id.clone(), fork.clone());
         Ok(())
     }
 
-    pub async fn get_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
+    async fn get_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
         let forks = self.forks.read().await;
         Ok(forks.get(fork_id).cloned())
     }
 
-    /// Refresh TTL and update expires_at to now + 15 minutes. Returns updated ForkInfo if present.
-    pub async fn refresh_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
+    async fn list_forks(&self) -> Result<Vec<ForkInfo>> {
+        let forks = self.forks.read().await;
+        Ok(forks.values().cloned().collect())
+    }
+
+    async fn refresh_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
         let mut forks = self.forks.write().await;
         if let Some(info) = forks.get_mut(fork_id) {
-            info.expires_at = chrono::Utc::now() + chrono::Duration::minutes(15);
+            info.refresh();
             Ok(Some(info.clone()))
         } else {
             Ok(None)
         }
     }
 
-    pub async fn delete_fork(&self, fork_id: &ForkId) -> Result<()> {
+    async fn delete_fork(&self, fork_id: &ForkId) -> Result<()> {
+        let mut forks = self.forks.write().await;
+        forks.remove(fork_id);
+        Ok(())
+    }
+}
+
+/// Stores forks on disk so they survive restarts.
+///
+/// Layout, one directory per fork:
+/// - `<root>/<fork_id>/info.json`: fork metadata
+/// - `<root>/<fork_id>/manifest.json`: archive manifest (blockhash, clock)
+/// - `<root>/<fork_id>/transactions.jsonl`: transaction history, one record per line, appended
+///   on every save so saves don't grow with the fork's history
+/// - `<root>/<fork_id>/accounts/<pubkey>`: bincode-encoded `Account`
+/// - `<root>/<fork_id>/origin.bin`: fork archive of the state the fork was created with
+///
+/// Metadata is loaded on open; account state is only read when a fork is first accessed.
+pub struct FileStorage {
+    root: PathBuf,
+    forks: RwLock<HashMap<ForkId, ForkInfo>>,
+}
+
+impl FileStorage {
+    /// Open (or create) a data directory and load the metadata of all stored forks
+    pub async fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        tokio::fs::create_dir_all(&root).await?;
+
+        let mut forks = HashMap::new();
+        let mut entries = tokio::fs::read_dir(&root).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path().join("info.json");
+            if !tokio::fs::try_exists(&path).await? {
+                continue;
+            }
+            match tokio::fs::read(&path)
+                .await
+                .map_err(anyhow::Error::from)
+                .and_then(|bytes| Ok(serde_json::from_slice::<ForkInfo>(&bytes)?))
+            {
+                Ok(info) => {
+                    forks.insert(info.fork_id.clone(), info);
+                }
+                Err(e) => warn!(
+                    "Skipping unreadable fork metadata {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        info!("Loaded {} forks from {}", forks.len(), root.display());
+        Ok(Self {
+            root,
+            forks: RwLock::new(forks),
+        })
+    }
+
+    fn fork_dir(&self, fork_id: &ForkId) -> PathBuf {
+        self.root.join(fork_id.to_string())
+    }
+
+    async fn write_info(&self, info: &ForkInfo) -> Result<()> {
+        let dir = self.fork_dir(&info.fork_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        write_atomic(&dir.join("info.json"), &serde_json::to_vec_pretty(info)?).await
+    }
+}
+
+#[async_trait]
+impl Storage for FileStorage {
+    async fn save_fork(&self, fork: &ForkInfo) -> Result<()> {
+        self.write_info(fork).await?;
+        let mut forks = self.forks.write().await;
+        forks.insert(fork.fork_id.clone(), fork.clone());
+        Ok(())
+    }
+
+    async fn get_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
+        let forks = self.forks.read().await;
+        Ok(forks.get(fork_id).cloned())
+    }
+
+    async fn list_forks(&self) -> Result<Vec<ForkInfo>> {
+        let forks = self.forks.read().await;
+        Ok(forks.values().cloned().collect())
+    }
+
+    async fn refresh_fork(&self, fork_id: &ForkId) -> Result<Option<ForkInfo>> {
+        let mut forks = self.forks.write().await;
+        let Some(info) = forks.get_mut(fork_id) else {
+            return Ok(None);
+        };
+        info.refresh();
+        let info = info.clone();
+        drop(forks);
+
+        self.write_info(&info).await?;
+        Ok(Some(info))
+    }
+
+    async fn delete_fork(&self, fork_id: &ForkId) -> Result<()> {
         let mut forks = self.forks.write().await;
         forks.remove(fork_id);
+        drop(forks);
+
+        let dir = self.fork_dir(fork_id);
+        if tokio::fs::try_exists(&dir).await? {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
         Ok(())
     }
+
+    async fn delete_fork_state(&self, fork_id: &ForkId) -> Result<()> {
+        let dir = self.fork_dir(fork_id);
+        if !tokio::fs::try_exists(&dir).await? {
+            return Ok(());
+        }
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_name() == "info.json" {
+                continue;
+            }
+            if entry.file_type().await?.is_dir() {
+                tokio::fs::remove_dir_all(entry.path()).await?;
+            } else {
+                tokio::fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn persists_state(&self) -> bool {
+        true
+    }
+
+    async fn save_fork_state(
+        &self,
+        fork_id: &ForkId,
+        manifest: &ArchiveManifest,
+        accounts: &[(Pubkey, Option<Account>)],
+        transactions: &[TransactionRecord],
+        replace: bool,
+    ) -> Result<()> {
+        let dir = self.fork_dir(fork_id);
+        let accounts_dir = recover_accounts(&dir).await?;
+
+        // A full save is written next to the current accounts and swapped in afterwards
+        let target = if replace {
+            let staging = dir.join("accounts.new");
+            if tokio::fs::try_exists(&staging).await? {
+                tokio::fs::remove_dir_all(&staging).await?;
+            }
+            staging
+        } else {
+            accounts_dir.clone()
+        };
+        tokio::fs::create_dir_all(&target).await?;
+
+        for (pubkey, account) in accounts {
+            let path = target.join(pubkey.to_string());
+            match account {
+                Some(account) => write_atomic(&path, &bincode::serialize(account)?).await?,
+                None => {
+                    if tokio::fs::try_exists(&path).await? {
+                        tokio::fs::remove_file(&path).await?;
+                    }
+                }
+            }
+        }
+
+        // The current accounts are moved aside rather than deleted first, so an interrupted
+        // swap leaves a complete directory behind for `recover_accounts`
+        if replace {
+            let previous = dir.join("accounts.old");
+            let had_accounts = tokio::fs::try_exists(&accounts_dir).await?;
+            if had_accounts {
+                tokio::fs::rename(&accounts_dir, &previous).await?;
+            }
+            tokio::fs::rename(&target, &accounts_dir).await?;
+            if had_accounts {
+                tokio::fs::remove_dir_all(&previous).await?;
+            }
+        }
+
+        let history = dir.join(TRANSACTIONS_FILE);
+        if replace {
+            write_atomic(&history, &transaction_lines(transactions)?).await?;
+        } else if !transactions.is_empty() {
+            let mut file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&history)
+                .await?;
+            file.write_all(&transaction_lines(transactions)?).await?;
+            file.flush().await?;
+        }
+
+        write_atomic(&dir.join("manifest.json"), &serde_json::to_vec(manifest)?).await
+    }
+
+    async fn load_fork_state(&self, fork_id: &ForkId) -> Result<Option<ForkArchive>> {
+        let dir = self.fork_dir(fork_id);
+        let manifest_path = dir.join("manifest.json");
+        if !tokio::fs::try_exists(&manifest_path).await? {
+            return Ok(None);
+        }
+
+        let mut manifest: ArchiveManifest =
+            serde_json::from_slice(&tokio::fs::read(&manifest_path).await?)?;
+
+        let history = dir.join(TRANSACTIONS_FILE);
+        if tokio::fs::try_exists(&history).await? {
+            let lines = tokio::fs::read(&history).await?;
+            for line in lines.split(|byte| *byte == b'\n') {
+                // A torn last line is a record whose save never completed
+                match serde_json::from_slice(line) {
+                    Ok(record) => manifest.transactions.push(record),
+                    Err(_) if line.iter().all(u8::is_ascii_whitespace) => {}
+                    Err(e) => warn!(
+                        "Skipping unreadable transaction record of fork {}: {}",
+                        fork_id, e
+                    ),
+                }
+            }
+        }
+
+        let mut accounts = Vec::new();
+        let accounts_dir = recover_accounts(&dir).await?;
+        if tokio::fs::try_exists(&accounts_dir).await? {
+            let mut entries = tokio::fs::read_dir(&accounts_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name();
+                // Skip leftovers of interrupted atomic writes
+                let Some(pubkey) = name.to_str().and_then(|name| name.parse::<Pubkey>().ok())
+                else {
+                    continue;
+                };
+                let account: Account = bincode::deserialize(&tokio::fs::read(entry.path()).await?)
+                    .map_err(|e| anyhow::anyhow!("Invalid stored account {}: {}", pubkey, e))?;
+                accounts.push((pubkey, account));
+            }
+        }
+
+        Ok(Some(ForkArchive { manifest, accounts }))
+    }
+
+    async fn save_fork_origin(&self, fork_id: &ForkId, origin: &ForkArchive) -> Result<()> {
+        let dir = self.fork_dir(fork_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        write_atomic(&dir.join("origin.bin"), &origin.to_bytes()?).await
+    }
+
+    async fn load_fork_origin(&self, fork_id: &ForkId) -> Result<Option<ForkArchive>> {
+        let path = self.fork_dir(fork_id).join("origin.bin");
+        if !tokio::fs::try_exists(&path).await? {
+            return Ok(None);
+        }
+        Ok(Some(ForkArchive::from_bytes(
+            &tokio::fs::read(&path).await?,
+        )?))
+    }
+}
+
+/// Finish or roll back a swap of a fork's accounts directory that was interrupted, and return
+/// the accounts directory. A staged `accounts.new` is left for the next full save to replace.
+async fn recover_accounts(dir: &Path) -> Result<PathBuf> {
+    let accounts_dir = dir.join("accounts");
+    let previous = dir.join("accounts.old");
+    if tokio::fs::try_exists(&previous).await? {
+        if tokio::fs::try_exists(&accounts_dir).await? {
+            // Only removing the previous accounts was interrupted
+            tokio::fs::remove_dir_all(&previous).await?;
+        } else {
+            // The new accounts were never moved in; the manifest still describes the previous
+            warn!(
+                "Restoring accounts of {} after an interrupted save",
+                dir.display()
+            );
+            tokio::fs::rename(&previous, &accounts_dir).await?;
+        }
+    }
+    Ok(accounts_dir)
+}
+
+/// Transaction records as JSON lines
+fn transaction_lines(transactions: &[TransactionRecord]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for record in transactions {
+        serde_json::to_writer(&mut bytes, record)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+/// Write a file by writing a temporary sibling and renaming it into place. Every write uses
+/// its own temporary file, so concurrent writes of one path don't rename each other's.
+async fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+    let tmp = path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    tokio::fs::write(&tmp, bytes).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{archive::ARCHIVE_VERSION, BlockProduction};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("sol-sim-storage-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn manifest(fork_id: &ForkId) -> ArchiveManifest {
+        ArchiveManifest {
+            version: ARCHIVE_VERSION,
+            fork_id: fork_id.to_string(),
+            created_at: chrono::Utc::now(),
+            exported_at: chrono::Utc::now(),
+            account_count: 0,
+            blockhash: String::new(),
+            recent_blockhashes: Vec::new(),
+            pending_clock_ms: 0,
+            block_production: BlockProduction::default(),
+            transactions: Vec::new(),
+            source_slot: None,
+            commitment: Default::default(),
+            cluster: None,
+            upstream_url: None,
+            idl: false,
+            limits: Default::default(),
+            source_accounts: Vec::new(),
+            lazy: false,
+            missing_accounts: Vec::new(),
+        }
+    }
+
+    fn record(slot: u64) -> TransactionRecord {
+        TransactionRecord {
+            signature: format!("sig{}", slot),
+            slot,
+            success: true,
+            error: None,
+            logs: Vec::new(),
+            compute_units_consumed: 0,
+        }
+    }
+
+    fn slots(archive: &ForkArchive) -> Vec<u64> {
+        archive
+            .manifest
+            .transactions
+            .iter()
+            .map(|record| record.slot)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn concurrent_refreshes_of_one_fork_succeed() {
+        let root = temp_dir();
+        let storage = Arc::new(FileStorage::open(&root).await.unwrap());
+        let info = ForkInfo::new(ForkId::new(), "http://localhost", 0, 15);
+        storage.save_fork(&info).await.unwrap();
+
+        let refreshes: Vec<_> = (0..32)
+            .map(|_| {
+                let storage = storage.clone();
+                let fork_id = info.fork_id.clone();
+                tokio::spawn(async move { storage.refresh_fork(&fork_id).await })
+            })
+            .collect();
+        for refresh in refreshes {
+            assert!(refresh.await.unwrap().unwrap().is_some());
+        }
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn transactions_are_appended_and_replaced() {
+        let root = temp_dir();
+        let storage = FileStorage::open(&root).await.unwrap();
+        let fork_id = ForkId::new();
+        let manifest = manifest(&fork_id);
+
+        storage
+            .save_fork_state(&fork_id, &manifest, &[], &[record(1)], true)
+            .await
+            .unwrap();
+        storage
+            .save_fork_state(&fork_id, &manifest, &[], &[record(2), record(3)], false)
+            .await
+            .unwrap();
+        let archive = storage.load_fork_state(&fork_id).await.unwrap().unwrap();
+        assert_eq!(slots(&archive), vec![1, 2, 3]);
+
+        storage
+            .save_fork_state(&fork_id, &manifest, &[], &[record(4)], true)
+            .await
+            .unwrap();
+        let archive = storage.load_fork_state(&fork_id).await.unwrap().unwrap();
+        assert_eq!(slots(&archive), vec![4]);
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn interrupted_account_swap_is_recovered() {
+        let root = temp_dir();
+        let storage = FileStorage::open(&root).await.unwrap();
+        let fork_id = ForkId::new();
+        let dir = storage.fork_dir(&fork_id);
+        let manifest = manifest(&fork_id);
+        let account = |lamports| Account {
+            lamports,
+            ..Default::default()
+        };
+        let (saved, staged) = (Pubkey::new_unique(), Pubkey::new_unique());
+        storage
+            .save_fork_state(&fork_id, &manifest, &[(saved, Some(account(1)))], &[], true)
+            .await
+            .unwrap();
+        let loaded = |archive: ForkArchive| {
+            archive
+                .accounts
+                .into_iter()
+                .map(|(pubkey, _)| pubkey)
+                .collect::<Vec<_>>()
+        };
+
+        // Interrupted after the current accounts were moved aside
+        tokio::fs::rename(dir.join("accounts"), dir.join("accounts.old"))
+            .await
+            .unwrap();
+        let staging = dir.join("accounts.new");
+        tokio::fs::create_dir_all(&staging).await.unwrap();
+        tokio::fs::write(
+            staging.join(staged.to_string()),
+            bincode::serialize(&account(2)).unwrap(),
+        )
+        .await
+        .unwrap();
+        let archive = storage.load_fork_state(&fork_id).await.unwrap().unwrap();
+        assert_eq!(loaded(archive), vec![saved]);
+        assert!(!tokio::fs::try_exists(dir.join("accounts.old"))
+            .await
+            .unwrap());
+
+        // Interrupted after the new accounts were moved in
+        tokio::fs::create_dir_all(dir.join("accounts.old"))
+            .await
+            .unwrap();
+        let archive = storage.load_fork_state(&fork_id).await.unwrap().unwrap();
+        assert_eq!(loaded(archive), vec![saved]);
+        assert!(!tokio::fs::try_exists(dir.join("accounts.old"))
+            .await
+            .unwrap());
+
+        // The next full save replaces the leftover staging directory
+        storage
+            .save_fork_state(
+                &fork_id,
+                &manifest,
+                &[(staged, Some(account(2)))],
+                &[],
+                true,
+            )
+            .await
+            .unwrap();
+        let archive = storage.load_fork_state(&fork_id).await.unwrap().unwrap();
+        assert_eq!(loaded(archive), vec![staged]);
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
+    }
 }
@@ -12,6 +12,12 @@ impl ForkId {
     }
 }
 
+impl Default for ForkId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl std::fmt::Display for ForkId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -25,6 +31,17 @@ impl std::str::FromStr for ForkId {
     }
 }
 
+/// Minutes a fork lives after creation or its last request unless configured otherwise
+pub const DEFAULT_TTL_MINUTES: i64 = 15;
+
+fn default_ttl_minutes() -> i64 {
+    DEFAULT_TTL_MINUTES
+}
+
+fn default_refresh_on_touch() -> bool {
+    true
+}
+
 /// Fork metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ForkInfo {
@@ -32,23 +49,80 @@ pub struct ForkInfo {
     pub rpc_url: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Minutes the fork lives after creation, an extension or, with `refresh_on_touch`, its
+    /// last request
+    #[serde(default = "default_ttl_minutes")]
+    pub ttl_minutes: i64,
+    /// Pinned forks never expire
+    #[serde(default)]
+    pub pinned: bool,
+    /// Requests to the fork push its expiry back by `ttl_minutes`
+    #[serde(default = "default_refresh_on_touch")]
+    pub refresh_on_touch: bool,
     pub account_count: usize,
+    /// Upstream slot the fork's accounts were fetched at; `None` if nothing was fetched
+    #[serde(default)]
+    pub source_slot: Option<u64>,
+    #[serde(default)]
+    pub commitment: Commitment,
+    /// Named upstream cluster the fork fetches accounts from
+    #[serde(default)]
+    pub cluster: Option<String>,
+    /// Ad-hoc upstream endpoint, used instead of a cluster
+    #[serde(default)]
+    pub upstream_url: Option<String>,
+    /// Anchor IDL accounts of programs are fetched along with them
+    #[serde(default)]
+    pub idl: bool,
+    /// Bounds the fork's accounts were fetched with; a refetch applies them again
+    #[serde(default)]
+    pub limits: FetchLimits,
+    /// Whether the clock, epoch schedule, slot hashes and blockhash were synced from upstream
+    #[serde(default)]
+    pub chain_sync: ChainSync,
+    /// The fork expired and its state was dropped; the metadata is kept so requests for it
+    /// fail as expired rather than not found
+    #[serde(default)]
+    pub evicted: bool,
 }
 
 impl ForkInfo {
-    pub fn new(fork_id: ForkId, base_url: &str, account_count: usize) -> Self {
+    pub fn new(fork_id: ForkId, base_url: &str, account_count: usize, ttl_minutes: i64) -> Self {
         let now = chrono::Utc::now();
         Self {
             fork_id: fork_id.clone(),
             rpc_url: format!("{}/rpc/{}", base_url, fork_id),
             created_at: now,
-            expires_at: now + chrono::Duration::minutes(15),
+            expires_at: now + chrono::Duration::minutes(ttl_minutes),
+            ttl_minutes,
+            pinned: false,
+            refresh_on_touch: true,
             account_count,
+            source_slot: None,
+            commitment: Commitment::default(),
+            cluster: None,
+            upstream_url: None,
+            idl: false,
+            limits: FetchLimits::default(),
+            chain_sync: ChainSync::default(),
+            evicted: false,
         }
     }
 
     pub fn is_expired(&self) -> bool {
-        chrono::Utc::now() > self.expires_at
+        self.evicted || (!self.pinned && chrono::Utc::now() > self.expires_at)
+    }
+
+    /// Expire `ttl_minutes` from now
+    pub fn extend(&mut self) {
+        self.expires_at = chrono::Utc::now() + chrono::Duration::minutes(self.ttl_minutes);
+    }
+
+    /// Extend the fork's lifetime after a request, unless `refresh_on_touch` is off
+    pub fn refresh(&mut self) {
+        if self.refresh_on_touch {
+            self.extend();
+        }
     }
 
     pub fn remaining_minutes(&self) -> i64 {
@@ -58,10 +132,247 @@ impl ForkInfo {
     }
 }
 
+/// Lifecycle notifications published by the fork manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum ForkEvent {
+    /// The fork outlived its TTL and was evicted
+    Expired {
+        #[serde(rename = "forkId")]
+        fork_id: ForkId,
+        #[serde(rename = "expiredAt")]
+        expired_at: chrono::DateTime<chrono::Utc>,
+    },
+}
+
+/// Outcome of syncing a fork's chain context from upstream
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChainSync {
+    pub synced: bool,
+    /// Why parts of the chain context kept liteSVM's defaults
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// How a fork advances its slot, clock and blockhash
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode")]
+pub enum BlockProduction {
+    /// Advance one slot after every transaction (blockhash unchanged)
+    #[default]
+    #[serde(rename = "perTx")]
+    PerTransaction,
+    /// Produce a new block every `interval_ms` milliseconds
+    #[serde(rename = "interval")]
+    Interval {
+        #[serde(rename = "intervalMs")]
+        interval_ms: u64,
+    },
+    /// Only produce blocks on `sim_mine`
+    #[serde(rename = "manual")]
+    Manual,
+}
+
+/// Upstream commitment level used when fetching accounts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Processed => "processed",
+            Self::Confirmed => "confirmed",
+            Self::Finalized => "finalized",
+        }
+    }
+}
+
+/// What `sim_refreshAccounts` does with accounts modified since the fork was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RefreshPolicy {
+    /// Replace local modifications with the upstream state
+    Overwrite,
+    /// Keep modified accounts as they are
+    #[default]
+    SkipModified,
+    /// Refresh nothing if a modified account differs from upstream
+    FailOnConflict,
+}
+
 /// API request/response types
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreateForkRequest {
     pub accounts: Vec<String>,
+    #[serde(rename = "blockProduction", default)]
+    pub block_production: BlockProduction,
+    #[serde(default)]
+    pub commitment: Commitment,
+    /// Upstream responses must be served at this slot or later
+    #[serde(rename = "minContextSlot")]
+    pub min_context_slot: Option<u64>,
+    /// Fetch accounts missing from the fork from upstream when they are first used
+    #[serde(default)]
+    pub lazy: bool,
+    /// Upstream cluster to fork from; the server's default cluster if omitted
+    pub cluster: Option<String>,
+    /// Upstream RPC endpoint to fork from, instead of a configured cluster
+    #[serde(rename = "rpcUrl")]
+    pub rpc_url: Option<String>,
+    /// Fetch every account from upstream instead of the shared account cache
+    #[serde(default)]
+    pub fresh: bool,
+    /// `getProgramAccounts` queries whose matching accounts are added to the fork
+    #[serde(rename = "programAccounts", default)]
+    pub program_accounts: Vec<ProgramAccountsQuery>,
+    /// Base64 serialized transaction whose accounts, including lookup table addresses, are
+    /// added to the fork
+    pub transaction: Option<String>,
+    /// Signature of an upstream transaction whose accounts are added to the fork
+    pub signature: Option<String>,
+    /// Execute `transaction` or `signature` once the fork is created, without checking its
+    /// blockhash
+    #[serde(default)]
+    pub execute: bool,
+    /// Also fetch the Anchor IDL account of every program the fork loads
+    #[serde(default)]
+    pub idl: bool,
+    #[serde(flatten)]
+    pub limits: FetchLimits,
+    /// Fail creation if a requested account doesn't exist upstream or an account can't be
+    /// decoded
+    #[serde(default)]
+    pub strict: bool,
+    /// Minutes the fork lives; the server default if omitted
+    #[serde(rename = "ttlMinutes")]
+    pub ttl_minutes: Option<i64>,
+    /// Exempt the fork from expiry
+    #[serde(default)]
+    pub pinned: bool,
+    /// Push the fork's expiry back on every request (default true)
+    #[serde(rename = "refreshOnTouch", default = "default_refresh_on_touch")]
+    pub refresh_on_touch: bool,
+}
+
+/// Body of `POST /forks/{fork_id}/extend`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExtendForkRequest {
+    /// New TTL of the fork; its current TTL if omitted
+    #[serde(rename = "ttlMinutes")]
+    pub ttl_minutes: Option<i64>,
+    /// Pin or unpin the fork
+    pub pinned: Option<bool>,
+}
+
+/// Bounds on dependency resolution when fetching a fork's accounts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchLimits {
+    /// Dependency levels to follow; requested accounts are depth 0
+    #[serde(rename = "maxDepth")]
+    pub max_depth: Option<usize>,
+    /// Owners and programs that are never fetched as dependencies
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Fetching fails if the accounts' data exceeds this many bytes in total
+    #[serde(rename = "maxBytes")]
+    pub max_bytes: Option<u64>,
+}
+
+/// Why an account is part of a fork
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InclusionReason {
+    /// Listed in `accounts`
+    Requested,
+    /// Matched a `programAccounts` query
+    ProgramAccounts,
+    /// Referenced by the transaction the fork is created from
+    Transaction,
+    /// Owner of another account
+    Owner,
+    /// Programdata of an upgradeable program
+    ProgramData,
+    /// Address stored in a lookup table
+    LookupTableEntry,
+    /// Mint of a token account
+    Mint,
+    /// Transfer hook program of a Token-2022 mint
+    TransferHook,
+    /// Extra account metas of a Token-2022 mint's transfer hook
+    ExtraAccountMetas,
+    /// Anchor IDL account of a program
+    Idl,
+}
+
+/// Accounts owned by a program, narrowed by `getProgramAccounts` filters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramAccountsQuery {
+    #[serde(rename = "programId")]
+    pub program_id: String,
+    /// All filters must match
+    #[serde(default)]
+    pub filters: Vec<ProgramAccountsFilter>,
+    /// Fork creation fails if more accounts match
+    #[serde(rename = "maxAccounts")]
+    pub max_accounts: Option<usize>,
+}
+
+/// `getProgramAccounts` filter, in the RPC's JSON format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProgramAccountsFilter {
+    /// Account data at `offset` equals `bytes`
+    #[serde(rename = "memcmp")]
+    Memcmp(Memcmp),
+    /// Account data is exactly this many bytes
+    #[serde(rename = "dataSize")]
+    DataSize(u64),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Memcmp {
+    pub offset: usize,
+    pub bytes: String,
+    #[serde(default)]
+    pub encoding: MemcmpEncoding,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemcmpEncoding {
+    #[default]
+    Base58,
+    Base64,
+}
+
+impl Memcmp {
+    pub fn decoded_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        use base64::Engine;
+        match self.encoding {
+            MemcmpEncoding::Base58 => Ok(bs58::decode(&self.bytes).into_vec()?),
+            MemcmpEncoding::Base64 => {
+                Ok(base64::engine::general_purpose::STANDARD.decode(&self.bytes)?)
+            }
+        }
+    }
+}
+
+impl ProgramAccountsFilter {
+    pub fn matches(&self, data: &[u8]) -> anyhow::Result<bool> {
+        Ok(match self {
+            Self::Memcmp(memcmp) => {
+                let bytes = memcmp.decoded_bytes()?;
+                data.get(memcmp.offset..)
+                    .is_some_and(|tail| tail.starts_with(&bytes))
+            }
+            Self::DataSize(size) => data.len() as u64 == *size,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,6 +390,68 @@ pub struct CreateForkResponse {
     pub account_count: usize,
     #[serde(rename = "ttlMinutes")]
     pub ttl_minutes: i64,
+    #[serde(rename = "sourceSlot")]
+    pub source_slot: Option<u64>,
+    /// Result of the transaction executed at creation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<TransactionRecord>,
+    /// Accounts fetched from upstream; absent for clones and imports
+    #[serde(flatten)]
+    pub report: Option<FetchReport>,
+}
+
+/// Account upstream returned that could not be decoded; it is not loaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountFailure {
+    pub pubkey: String,
+    pub error: String,
+}
+
+/// What fetching a fork's accounts loaded and what it couldn't
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FetchReport {
+    /// Accounts loaded into the fork, dependencies included, in loading order
+    #[serde(rename = "loadedAccounts")]
+    pub loaded: Vec<String>,
+    /// Requested accounts upstream reported as non-existent
+    #[serde(rename = "missingAccounts")]
+    pub missing: Vec<String>,
+    /// Accounts, dependencies included, that could not be decoded
+    #[serde(rename = "failedAccounts")]
+    pub failed: Vec<AccountFailure>,
+}
+
+/// Account a fork would be created with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedAccount {
+    pub pubkey: String,
+    pub owner: String,
+    pub executable: bool,
+    /// Data size in bytes
+    pub size: usize,
+    pub reason: InclusionReason,
+    /// Account this one is a dependency of
+    #[serde(rename = "dependencyOf", skip_serializing_if = "Option::is_none")]
+    pub dependency_of: Option<String>,
+    /// Dependency levels from the accounts the request names
+    pub depth: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForkPlanResponse {
+    pub success: bool,
+    /// Accounts in loading order
+    pub accounts: Vec<PlannedAccount>,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    #[serde(rename = "sourceSlot")]
+    pub source_slot: Option<u64>,
+    /// Requested accounts upstream reported as non-existent
+    #[serde(rename = "missingAccounts")]
+    pub missing: Vec<String>,
+    /// Accounts that could not be decoded and would not be loaded
+    #[serde(rename = "failedAccounts")]
+    pub failed: Vec<AccountFailure>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -95,8 +468,61 @@ pub struct GetForkResponse {
     pub expires_at: chrono::DateTime<chrono::Utc>,
     #[serde(rename = "remainingMinutes")]
     pub remaining_minutes: i64,
+    #[serde(rename = "ttlMinutes")]
+    pub ttl_minutes: i64,
+    pub pinned: bool,
+    #[serde(rename = "refreshOnTouch")]
+    pub refresh_on_touch: bool,
     #[serde(rename = "accountCount")]
     pub account_count: usize,
+    #[serde(rename = "sourceSlot")]
+    pub source_slot: Option<u64>,
+    pub commitment: Commitment,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    #[serde(rename = "upstreamUrl", skip_serializing_if = "Option::is_none")]
+    pub upstream_url: Option<String>,
+    pub idl: bool,
+    pub limits: FetchLimits,
+    #[serde(rename = "chainSync")]
+    pub chain_sync: ChainSync,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ForkDiffResponse {
+    pub success: bool,
+    #[serde(rename = "forkId")]
+    pub fork_id: String,
+    #[serde(flatten)]
+    pub diff: crate::diff::ForkDiff,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PatchForkResponse {
+    pub success: bool,
+    #[serde(rename = "forkId")]
+    pub fork_id: String,
+    pub created: usize,
+    pub deleted: usize,
+    pub modified: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ResetForkQuery {
+    /// Pull the accounts requested at creation from upstream again
+    #[serde(default)]
+    pub refetch: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResetForkResponse {
+    pub success: bool,
+    #[serde(rename = "forkId")]
+    pub fork_id: String,
+    #[serde(rename = "rpcUrl")]
+    pub rpc_url: String,
+    pub slot: u64,
+    pub refetched: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -116,6 +542,16 @@ pub struct HealthResponse {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheStatsResponse {
+    pub success: bool,
+    pub entries: usize,
+    pub bytes: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub success: bool,
@@ -130,7 +566,7 @@ pub struct ErrorDetails {
     pub details: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccountData {
     pub lamports: u64,
     pub data: String, // base64
@@ -161,6 +597,47 @@ impl AccountData {
     }
 }
 
+/// Record of a transaction executed on a fork
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionRecord {
+    pub signature: String,
+    pub slot: u64,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub logs: Vec<String>,
+    #[serde(rename = "computeUnitsConsumed")]
+    pub compute_units_consumed: u64,
+}
+
+impl TransactionRecord {
+    pub fn new(slot: u64, result: &litesvm::types::TransactionResult) -> Self {
+        let (meta, error) = match result {
+            Ok(meta) => (meta, None),
+            Err(failed) => (&failed.meta, Some(failed.err.to_string())),
+        };
+        Self {
+            signature: meta.signature.to_string(),
+            slot,
+            success: error.is_none(),
+            error,
+            logs: meta.logs.clone(),
+            compute_units_consumed: meta.compute_units_consumed,
+        }
+    }
+}
+
+/// Parameters for the `sim_setOraclePrice` cheat
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OraclePriceParams {
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    /// Defaults to the fork's current `Clock::unix_timestamp`
+    #[serde(rename = "publishTime")]
+    pub publish_time: Option<i64>,
+}
+
 /// JSON-RPC types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcRequest {
@@ -0,0 +1,744 @@
+use crate::fixtures::Fixtures;
+use anyhow::Result;
+use rand::Rng;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tracing::warn;
+
+/// Longest `Retry-After` honored; longer waits are capped so a request can't hang indefinitely
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// JSON-RPC errors worth retrying: node behind (-32005) and min context slot not reached (-32016)
+const RETRYABLE_RPC_ERRORS: [i64; 2] = [-32005, -32016];
+
+/// Timeouts and retry policy for upstream requests
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamConfig {
+    /// Timeout for a single request, including reading the response
+    pub timeout: Duration,
+    /// Retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry; doubles with every further retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the backoff delay
+    pub max_backoff: Duration,
+}
+
+impl Default for UpstreamConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+/// One failed attempt, and how long to wait before the next one if it can be retried
+struct Failure {
+    error: anyhow::Error,
+    retry_after: Option<Duration>,
+    retryable: bool,
+}
+
+impl Failure {
+    fn fatal(error: anyhow::Error) -> Self {
+        Self {
+            error,
+            retry_after: None,
+            retryable: false,
+        }
+    }
+
+    fn transient(error: anyhow::Error) -> Self {
+        Self {
+            error,
+            retry_after: None,
+            retryable: true,
+        }
+    }
+}
+
+/// Name of the only cluster when the server is started with `--solana-rpc` alone
+pub const DEFAULT_CLUSTER: &str = "default";
+
+/// Name of the cluster added by `--fixtures`
+pub const FIXTURES_CLUSTER: &str = "fixtures";
+
+/// How a cluster with several endpoints spreads requests over them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Strategy {
+    /// Always start with the first endpoint and move on when it fails
+    #[default]
+    Failover,
+    /// Start each request with the next endpoint in turn, moving on when it fails
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EndpointConfig {
+    pub url: String,
+    /// Sent with every request, e.g. an API key
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+}
+
+/// A cluster is served either by RPC endpoints or by a fixture directory
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClusterConfig {
+    #[serde(default)]
+    pub endpoints: Vec<EndpointConfig>,
+    #[serde(default)]
+    pub strategy: Strategy,
+    /// Directory of account files served instead of an RPC, see [`Fixtures`]
+    #[serde(default)]
+    pub fixtures: Option<PathBuf>,
+}
+
+impl ClusterConfig {
+    pub fn rpc(url: String) -> Self {
+        Self {
+            endpoints: vec![EndpointConfig {
+                url,
+                headers: HashMap::new(),
+            }],
+            strategy: Strategy::default(),
+            fixtures: None,
+        }
+    }
+
+    pub fn fixtures(dir: PathBuf) -> Self {
+        Self {
+            endpoints: Vec::new(),
+            strategy: Strategy::default(),
+            fixtures: Some(dir),
+        }
+    }
+}
+
+/// Contents of the `--upstreams` file
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpstreamsConfig {
+    /// Cluster used by forks that don't name one; optional with a single cluster
+    pub default: Option<String>,
+    pub clusters: HashMap<String, ClusterConfig>,
+    /// Hosts a fork's `rpcUrl` may point at; `*` allows any. Empty disables `rpcUrl`.
+    #[serde(default, rename = "rpcUrlHosts")]
+    pub rpc_url_hosts: Vec<String>,
+}
+
+impl UpstreamsConfig {
+    /// A single cluster named [`DEFAULT_CLUSTER`] with one endpoint, used by default
+    pub fn single(url: String) -> Self {
+        Self {
+            default: Some(DEFAULT_CLUSTER.to_string()),
+            clusters: HashMap::from([(DEFAULT_CLUSTER.to_string(), ClusterConfig::rpc(url))]),
+            rpc_url_hosts: Vec::new(),
+        }
+    }
+
+    /// Read clusters from a JSON file
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|e| anyhow::anyhow!("Invalid upstreams file {}: {}", path.display(), e))
+    }
+}
+
+/// A fork's `rpcUrl` points at a host the operator hasn't allowed
+#[derive(Debug)]
+pub struct RpcUrlNotAllowed(pub String);
+
+impl std::fmt::Display for RpcUrlNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rpcUrl '{}' is not allowed by this server", self.0)
+    }
+}
+
+impl std::error::Error for RpcUrlNotAllowed {}
+
+/// Named upstream clusters sharing one pooled HTTP client
+pub struct Upstreams {
+    clusters: HashMap<String, UpstreamClient>,
+    default: String,
+    rpc_url_hosts: Vec<String>,
+    http: reqwest::Client,
+    config: UpstreamConfig,
+}
+
+impl Upstreams {
+    /// A single cluster named [`DEFAULT_CLUSTER`] with one endpoint
+    pub fn single(url: String, config: UpstreamConfig) -> Result<Self> {
+        Self::new(UpstreamsConfig::single(url), config)
+    }
+
+    pub fn new(upstreams: UpstreamsConfig, config: UpstreamConfig) -> Result<Self> {
+        let default = match upstreams.default {
+            Some(default) => default,
+            None if upstreams.clusters.len() == 1 => {
+                upstreams.clusters.keys().next().unwrap().clone()
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                    "A default cluster is required when more than one cluster is configured"
+                ))
+            }
+        };
+        if !upstreams.clusters.contains_key(&default) {
+            return Err(anyhow::anyhow!(
+                "Default cluster '{}' is not configured",
+                default
+            ));
+        }
+
+        let http = reqwest::Client::builder().timeout(config.timeout).build()?;
+        let mut clusters = HashMap::new();
+        for (name, cluster) in upstreams.clusters {
+            let fixtures = match (&cluster.fixtures, cluster.endpoints.is_empty()) {
+                (Some(_), false) => {
+                    return Err(anyhow::anyhow!(
+                        "Cluster '{}' has both endpoints and fixtures",
+                        name
+                    ))
+                }
+                (Some(dir), true) => Some(Arc::new(Fixtures::load(dir)?)),
+                (None, false) => None,
+                (None, true) => return Err(anyhow::anyhow!("Cluster '{}' has no endpoints", name)),
+            };
+            let endpoints = cluster
+                .endpoints
+                .into_iter()
+                .map(|endpoint| {
+                    Endpoint::new(endpoint).map_err(|e| {
+                        anyhow::anyhow!("Invalid endpoint in cluster '{}': {}", name, e)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let client = UpstreamClient {
+                id: format!("cluster:{}", name),
+                endpoints: endpoints.into(),
+                strategy: cluster.strategy,
+                next: Arc::new(AtomicUsize::new(0)),
+                fixtures,
+                http: http.clone(),
+                config,
+            };
+            clusters.insert(name, client);
+        }
+
+        Ok(Self {
+            clusters,
+            default,
+            rpc_url_hosts: upstreams
+                .rpc_url_hosts
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            http,
+            config,
+        })
+    }
+
+    pub fn default_cluster(&self) -> &str {
+        &self.default
+    }
+
+    /// Configured cluster names with a description of their source, sorted by name
+    pub fn clusters(&self) -> Vec<(&str, String)> {
+        let mut clusters: Vec<_> = self
+            .clusters
+            .iter()
+            .map(|(name, client)| {
+                let source = match &client.fixtures {
+                    Some(fixtures) => format!("{} fixture account(s)", fixtures.len()),
+                    None => format!("{} endpoint(s)", client.endpoints.len()),
+                };
+                (name.as_str(), source)
+            })
+            .collect();
+        clusters.sort();
+        clusters
+    }
+
+    /// Hosts a fork's `rpcUrl` may point at, lowercased; `*` allows any
+    pub fn rpc_url_hosts(&self) -> &[String] {
+        &self.rpc_url_hosts
+    }
+
+    pub fn cluster(&self, name: &str) -> Result<UpstreamClient> {
+        self.clusters
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Unknown cluster '{}'", name))
+    }
+
+    /// Client for an ad-hoc endpoint, using the shared connection pool and retry policy.
+    /// Fails with [`RpcUrlNotAllowed`] unless the operator allowed the endpoint's host.
+    pub fn url(&self, url: &str) -> Result<UpstreamClient> {
+        let endpoint = Endpoint::new(EndpointConfig {
+            url: url.to_string(),
+            headers: HashMap::new(),
+        })?;
+        let host = endpoint.url.host_str().unwrap_or_default();
+        if !self
+            .rpc_url_hosts
+            .iter()
+            .any(|allowed| allowed == "*" || host.eq_ignore_ascii_case(allowed))
+        {
+            return Err(RpcUrlNotAllowed(url.to_string()).into());
+        }
+        Ok(UpstreamClient {
+            id: format!("url:{}", url),
+            endpoints: vec![endpoint].into(),
+            strategy: Strategy::Failover,
+            next: Arc::new(AtomicUsize::new(0)),
+            fixtures: None,
+            http: self.http.clone(),
+            config: self.config,
+        })
+    }
+}
+
+struct Endpoint {
+    url: reqwest::Url,
+    headers: HeaderMap,
+}
+
+impl Endpoint {
+    fn new(config: EndpointConfig) -> Result<Self> {
+        let url = reqwest::Url::parse(&config.url)
+            .map_err(|e| anyhow::anyhow!("Invalid URL '{}': {}", config.url, e))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow::anyhow!(
+                "URL '{}' must be http or https",
+                config.url
+            ));
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in config.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|e| anyhow::anyhow!("Invalid header name '{}': {}", name, e))?,
+                HeaderValue::from_str(&value)
+                    .map_err(|e| anyhow::anyhow!("Invalid value for header '{}': {}", name, e))?,
+            );
+        }
+        Ok(Self { url, headers })
+    }
+}
+
+/// JSON-RPC client for one upstream cluster. Connections are pooled; timeouts, 5xx
+/// responses, HTTP 429 and transient RPC errors move on to the cluster's next endpoint, and
+/// once every endpoint was tried are retried with exponential backoff and jitter, honoring
+/// `Retry-After`. A cluster configured with fixtures answers from them instead and never
+/// touches the network.
+#[derive(Clone)]
+pub struct UpstreamClient {
+    /// Identifies the upstream, e.g. to key cached accounts
+    id: String,
+    endpoints: Arc<[Endpoint]>,
+    strategy: Strategy,
+    /// Endpoint the next round-robin request starts with
+    next: Arc<AtomicUsize>,
+    fixtures: Option<Arc<Fixtures>>,
+    http: reqwest::Client,
+    config: UpstreamConfig,
+}
+
+impl UpstreamClient {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Call `method` and return its `result`
+    pub async fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        if let Some(fixtures) = &self.fixtures {
+            return fixtures
+                .call(method, &params)
+                .map_err(|e| anyhow::anyhow!("{} failed: {}", method, e));
+        }
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params
+        });
+
+        let count = self.endpoints.len();
+        let start = match self.strategy {
+            Strategy::Failover => 0,
+            Strategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % count,
+        };
+        // Every endpoint gets at least one attempt
+        let max_attempts = (self.config.max_retries as usize + 1).max(count);
+
+        let mut attempt = 0;
+        loop {
+            let endpoint = &self.endpoints[(start + attempt) % count];
+            let failure = match self.attempt(endpoint, &body).await {
+                Ok(result) => return Ok(result),
+                Err(failure) => failure,
+            };
+            if !failure.retryable {
+                return Err(anyhow::anyhow!("{} failed: {}", method, failure.error));
+            }
+            attempt += 1;
+            if attempt >= max_attempts {
+                return Err(anyhow::anyhow!(
+                    "{} failed after {} attempts: {}",
+                    method,
+                    attempt,
+                    failure.error
+                ));
+            }
+
+            // Untried endpoints are tried right away; after that, back off
+            if attempt < count {
+                warn!(
+                    "Upstream {} failed ({}), trying next endpoint",
+                    method, failure.error
+                );
+                continue;
+            }
+            let delay = self.retry_delay(failure.retry_after, (attempt - count) as u32);
+            warn!(
+                "Upstream {} failed ({}), retrying in {}ms",
+                method,
+                failure.error,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn attempt(
+        &self,
+        endpoint: &Endpoint,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, Failure> {
+        let response = self
+            .http
+            .post(endpoint.url.clone())
+            .headers(endpoint.headers.clone())
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| Failure::transient(request_error(e)))?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(Failure {
+                retry_after: retry_after(&response),
+                ..Failure::transient(anyhow::anyhow!(
+                    "upstream rate limited the request (HTTP 429)"
+                ))
+            });
+        }
+        if status.is_server_error() {
+            return Err(Failure::transient(anyhow::anyhow!(
+                "upstream returned HTTP {}",
+                status
+            )));
+        }
+        if !status.is_success() {
+            return Err(Failure::fatal(anyhow::anyhow!(
+                "upstream returned HTTP {}",
+                status
+            )));
+        }
+
+        let mut data: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| Failure::transient(request_error(e)))?;
+
+        if let Some(error) = data.get("error").filter(|error| !error.is_null()) {
+            let code = error["code"].as_i64().unwrap_or(0);
+            let message = error["message"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| error.to_string());
+            return Err(Failure {
+                error: anyhow::anyhow!("upstream RPC error {}: {}", code, message),
+                retry_after: None,
+                retryable: RETRYABLE_RPC_ERRORS.contains(&code),
+            });
+        }
+
+        match data.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(Failure::fatal(anyhow::anyhow!(
+                "upstream response has neither result nor error"
+            ))),
+        }
+    }
+
+    /// Delay before a retry: the upstream's `Retry-After`, capped at `MAX_RETRY_AFTER`,
+    /// otherwise the backoff for the `retry`th retry
+    fn retry_delay(&self, retry_after: Option<Duration>, retry: u32) -> Duration {
+        retry_after
+            .map(|delay| delay.min(MAX_RETRY_AFTER))
+            .unwrap_or_else(|| self.backoff(retry))
+    }
+
+    /// Exponential backoff, jittered down by up to half
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .config
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.config.max_backoff);
+        ceiling.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+fn request_error(e: reqwest::Error) -> anyhow::Error {
+    if e.is_timeout() {
+        anyhow::anyhow!("upstream request timed out")
+    } else {
+        anyhow::anyhow!("upstream request failed: {}", e.without_url())
+    }
+}
+
+/// `Retry-After` in seconds; HTTP dates are not supported and fall back to backoff
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, response::IntoResponse, routing::post, Json, Router};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    /// What a mock endpoint answers to its next request
+    enum Reply {
+        Result(serde_json::Value),
+        RpcError(i64),
+        Status(u16),
+        /// HTTP 429 with `Retry-After` in seconds
+        RateLimited(u64),
+        /// Never answers within the client's timeout
+        Hang,
+    }
+
+    /// An endpoint answering with scripted replies, then HTTP 500 once they run out
+    struct Mock {
+        replies: Mutex<VecDeque<Reply>>,
+        requests: AtomicUsize,
+    }
+
+    impl Mock {
+        fn requests(&self) -> usize {
+            self.requests.load(Ordering::SeqCst)
+        }
+    }
+
+    async fn reply(State(mock): State<Arc<Mock>>) -> axum::response::Response {
+        mock.requests.fetch_add(1, Ordering::SeqCst);
+        let reply = mock.replies.lock().unwrap().pop_front();
+        match reply {
+            Some(Reply::Result(result)) => {
+                Json(json!({"jsonrpc": "2.0", "id": 1, "result": result})).into_response()
+            }
+            Some(Reply::RpcError(code)) => Json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "error": {"code": code, "message": "mock error"}
+            }))
+            .into_response(),
+            Some(Reply::Status(status)) => axum::http::StatusCode::from_u16(status)
+                .unwrap()
+                .into_response(),
+            Some(Reply::RateLimited(secs)) => (
+                axum::http::StatusCode::TOO_MANY_REQUESTS,
+                [(axum::http::header::RETRY_AFTER, secs.to_string())],
+            )
+                .into_response(),
+            Some(Reply::Hang) => {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                axum::http::StatusCode::OK.into_response()
+            }
+            None => axum::http::StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+
+    async fn serve(replies: Vec<Reply>) -> (String, Arc<Mock>) {
+        let mock = Arc::new(Mock {
+            replies: Mutex::new(replies.into()),
+            requests: AtomicUsize::new(0),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new()
+            .route("/", post(reply))
+            .with_state(mock.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, mock)
+    }
+
+    /// Short timeouts and backoff so failing requests are retried quickly
+    fn config() -> UpstreamConfig {
+        UpstreamConfig {
+            timeout: Duration::from_millis(200),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        }
+    }
+
+    fn client(urls: &[String], strategy: Strategy, config: UpstreamConfig) -> UpstreamClient {
+        let cluster = ClusterConfig {
+            endpoints: urls
+                .iter()
+                .map(|url| EndpointConfig {
+                    url: url.clone(),
+                    headers: HashMap::new(),
+                })
+                .collect(),
+            strategy,
+            fixtures: None,
+        };
+        let upstreams = UpstreamsConfig {
+            default: None,
+            clusters: HashMap::from([("test".to_string(), cluster)]),
+            rpc_url_hosts: Vec::new(),
+        };
+        Upstreams::new(upstreams, config)
+            .unwrap()
+            .cluster("test")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_timeouts_and_server_errors() {
+        let (url, mock) = serve(vec![
+            Reply::Hang,
+            Reply::Status(503),
+            Reply::Result(json!(42)),
+        ])
+        .await;
+        let upstream = client(&[url], Strategy::Failover, config());
+
+        assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 42);
+        assert_eq!(mock.requests(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let (url, mock) = serve(Vec::new()).await;
+        let upstream = client(&[url], Strategy::Failover, config());
+
+        let error = upstream.call("getSlot", json!([])).await.unwrap_err();
+        assert!(error.to_string().contains("after 4 attempts"), "{}", error);
+        assert_eq!(mock.requests(), 4);
+    }
+
+    #[tokio::test]
+    async fn honors_retry_after() {
+        let (url, mock) = serve(vec![Reply::RateLimited(1), Reply::Result(json!(42))]).await;
+        // Backoff alone would wait far longer than the upstream asked for
+        let upstream = client(
+            &[url],
+            Strategy::Failover,
+            UpstreamConfig {
+                initial_backoff: Duration::from_secs(20),
+                max_backoff: Duration::from_secs(20),
+                ..config()
+            },
+        );
+
+        let started = Instant::now();
+        assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 42);
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(1), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+        assert_eq!(mock.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn caps_retry_after() {
+        let upstream = client(
+            &["http://127.0.0.1:1".to_string()],
+            Strategy::Failover,
+            config(),
+        );
+
+        assert_eq!(
+            upstream.retry_delay(Some(Duration::from_secs(3600)), 0),
+            MAX_RETRY_AFTER
+        );
+        assert_eq!(
+            upstream.retry_delay(Some(Duration::from_secs(2)), 0),
+            Duration::from_secs(2)
+        );
+        assert!(upstream.retry_delay(None, 10) <= config().max_backoff);
+    }
+
+    #[tokio::test]
+    async fn retries_transient_rpc_errors_only() {
+        for code in RETRYABLE_RPC_ERRORS {
+            let (url, mock) = serve(vec![Reply::RpcError(code), Reply::Result(json!(42))]).await;
+            let upstream = client(&[url], Strategy::Failover, config());
+            assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 42);
+            assert_eq!(mock.requests(), 2);
+        }
+
+        // Invalid params, and HTTP errors other than 429 and 5xx, are not retried
+        for reply in [Reply::RpcError(-32602), Reply::Status(400)] {
+            let (url, mock) = serve(vec![reply, Reply::Result(json!(42))]).await;
+            let upstream = client(&[url], Strategy::Failover, config());
+            assert!(upstream.call("getSlot", json!([])).await.is_err());
+            assert_eq!(mock.requests(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_over_in_order() {
+        let (first_url, first) = serve(vec![Reply::Status(503), Reply::Result(json!(3))]).await;
+        let (second_url, second) = serve(vec![Reply::Result(json!(2))]).await;
+        let upstream = client(&[first_url, second_url], Strategy::Failover, config());
+
+        // A failed endpoint moves on to the next one; the next request starts at the first again
+        assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 2);
+        assert_eq!((first.requests(), second.requests()), (1, 1));
+        assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 3);
+        assert_eq!((first.requests(), second.requests()), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn round_robin_rotates_endpoints() {
+        let (first_url, first) = serve(vec![Reply::Result(json!(1))]).await;
+        let (second_url, second) = serve(vec![Reply::Result(json!(2))]).await;
+        let upstream = client(&[first_url, second_url], Strategy::RoundRobin, config());
+
+        assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 1);
+        assert_eq!(upstream.call("getSlot", json!([])).await.unwrap(), 2);
+        assert_eq!((first.requests(), second.requests()), (1, 1));
+    }
+}
@@ -5,12 +5,12 @@ use serde_json::json;
 use sol_sim::CreateForkResponse;
 use solana_sdk::{
     instruction::{AccountMeta, Instruction},
+    message::{v0, AddressLookupTableAccount, VersionedMessage},
     pubkey::Pubkey,
     signature::Keypair,
     signer::Signer,
-    transaction::Transaction,
+    transaction::{Transaction, VersionedTransaction},
 };
-use tokio;
 
 // System program ID constant
 const SYSTEM_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("11111111111111111111111111111111");
@@ -52,6 +52,17 @@ impl TestContext {
         Ok(response.json().await?)
     }
 
+    async fn create_fork_with(&self, request: serde_json::Value) -> Result<CreateForkResponse> {
+        let response = self
+            .client
+            .post(format!("{}/forks", self.base_url))
+            .json(&request)
+            .send()
+            .await?;
+
+        Ok(response.json().await?)
+    }
+
     async fn get_fork(&self, fork_id: &str) -> Result<serde_json::Value> {
         let response = self
             .client
@@ -92,6 +103,41 @@ impl TestContext {
     }
 }
 
+/// A server process of the test's own, for tests that need particular flags or a restart.
+/// Killed when dropped.
+struct TestServer {
+    process: std::process::Child,
+}
+
+impl TestServer {
+    async fn start(port: u16, args: &[&str]) -> Result<Self> {
+        let process = std::process::Command::new(env!("CARGO_BIN_EXE_sol-sim"))
+            .args(["--port", &port.to_string()])
+            .args(["--solana-rpc", "http://127.0.0.1:1"])
+            .args(args)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()?;
+        let server = Self { process };
+
+        let health = format!("http://localhost:{}/health", port);
+        for _ in 0..100 {
+            if Client::new().get(&health).send().await.is_ok() {
+                return Ok(server);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        anyhow::bail!("Server on port {} did not start", port)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
 #[tokio::test]
 async fn test_fork_lifecycle() -> Result<()> {
     let ctx = TestContext::new();
@@ -421,3 +467,1934 @@ async fn test_error_handling() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_set_oracle_price() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+
+    // Minimal PriceUpdateV2 account: discriminator, write authority, Full verification
+    let mut data = vec![34, 241, 35, 99, 157, 126, 244, 205];
+    data.extend_from_slice(&[0u8; 32]);
+    data.push(1);
+    data.resize(133, 0);
+
+    let oracle = Keypair::new().pubkey();
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            oracle.to_string(),
+            {
+                "lamports": 1_000_000_000u64,
+                "data": base64::engine::general_purpose::STANDARD.encode(&data),
+                "owner": "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    let response = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "sim_setOraclePrice",
+            json!([oracle.to_string(), {"price": 14_000_000_000i64, "conf": 1_000_000u64, "exponent": -8}]),
+        )
+        .await?;
+    assert_eq!(response["result"]["value"]["oracle"], "pythPriceUpdateV2");
+
+    let account = ctx
+        .rpc_call(&fork.fork_id, "getAccountInfo", json!([oracle.to_string()]))
+        .await?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(account["result"]["value"]["data"][0].as_str().unwrap())?;
+
+    // Price message starts at byte 41; price follows the 32-byte feed id
+    assert_eq!(i64::from_le_bytes(data[73..81].try_into()?), 14_000_000_000);
+    assert_eq!(i32::from_le_bytes(data[89..93].try_into()?), -8);
+
+    // Non-oracle accounts are rejected
+    let payer = Keypair::new();
+    let response = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "sim_setOraclePrice",
+            json!([payer.pubkey().to_string(), {"price": 1, "conf": 0, "exponent": 0}]),
+        )
+        .await?;
+    assert!(response["error"].is_object());
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+/// Helper to create an AdvanceNonceAccount instruction
+fn advance_nonce(nonce: &Pubkey, authority: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: SYSTEM_PROGRAM_ID,
+        accounts: vec![
+            AccountMeta::new(*nonce, false),
+            AccountMeta::new_readonly(
+                solana_sdk::pubkey!("SysvarRecentB1ockHashes11111111111111111111"),
+                false,
+            ),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data: vec![4, 0, 0, 0], // AdvanceNonceAccount instruction discriminator
+    }
+}
+
+/// Durable nonce stored in `nonce_account`
+async fn stored_nonce(
+    ctx: &TestContext,
+    fork_id: &str,
+    nonce_account: &Pubkey,
+) -> Result<solana_sdk::hash::Hash> {
+    let account = ctx
+        .rpc_call(
+            fork_id,
+            "getAccountInfo",
+            json!([nonce_account.to_string()]),
+        )
+        .await?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(account["result"]["value"]["data"][0].as_str().unwrap())?;
+    // Versions tag, State tag and authority precede the durable nonce
+    Ok(solana_sdk::hash::Hash::new_from_array(
+        data[40..72].try_into()?,
+    ))
+}
+
+/// Durable nonce a transaction executed against the fork's latest blockhash advances to
+async fn next_nonce(ctx: &TestContext, fork_id: &str) -> Result<solana_sdk::hash::Hash> {
+    let latest = ctx
+        .rpc_call(fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let blockhash = latest["result"]["value"]["blockhash"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+    Ok(*solana_nonce::state::DurableNonce::from_blockhash(&blockhash).as_hash())
+}
+
+/// Transfer 1 SOL from `payer` to `recipient` in a transaction using `nonce_account`'s
+/// current durable nonce
+async fn send_nonce_transfer(
+    ctx: &TestContext,
+    fork_id: &str,
+    nonce_account: &Pubkey,
+    payer: &Keypair,
+    recipient: &Keypair,
+) -> Result<serde_json::Value> {
+    let nonce = stored_nonce(ctx, fork_id, nonce_account).await?;
+
+    let instructions = vec![
+        advance_nonce(nonce_account, &payer.pubkey()),
+        transfer(&payer.pubkey(), &recipient.pubkey(), 1_000_000_000),
+    ];
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
+    transaction.sign(&[payer], nonce);
+
+    let serialized = bincode::serialize(&transaction)?;
+    let base64_tx = base64::engine::general_purpose::STANDARD.encode(serialized);
+    ctx.rpc_call(fork_id, "sendTransaction", json!([base64_tx]))
+        .await
+}
+
+#[tokio::test]
+async fn test_durable_nonce_transactions() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+
+    let payer = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 10_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    let created = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "sim_createNonceAccount",
+            json!([payer.pubkey().to_string()]),
+        )
+        .await?;
+    let nonce_account: Pubkey = created["result"]["value"]["nonceAccount"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+
+    // Each use advances the nonce to the durable nonce of the blockhash the transaction
+    // executed against, so the nonce can't be used again until a block is produced
+    for _ in 0..2 {
+        let blockhash = ctx
+            .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+            .await?;
+        let next = next_nonce(&ctx, &fork.fork_id).await?;
+        let tx_response =
+            send_nonce_transfer(&ctx, &fork.fork_id, &nonce_account, &payer, &recipient).await?;
+        assert!(tx_response["error"].is_null(), "{:?}", tx_response);
+        assert_eq!(
+            stored_nonce(&ctx, &fork.fork_id, &nonce_account).await?,
+            next
+        );
+
+        let tx_response =
+            send_nonce_transfer(&ctx, &fork.fork_id, &nonce_account, &payer, &recipient).await?;
+        assert!(
+            tx_response["error"]["message"]
+                .as_str()
+                .unwrap_or_default()
+                .contains("already used in this block"),
+            "{:?}",
+            tx_response
+        );
+        let after = ctx
+            .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+            .await?;
+        assert_eq!(
+            after["result"]["value"]["blockhash"],
+            blockhash["result"]["value"]["blockhash"]
+        );
+
+        ctx.rpc_call(&fork.fork_id, "sim_mine", json!([])).await?;
+    }
+
+    let balance = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "getBalance",
+            json!([recipient.pubkey().to_string()]),
+        )
+        .await?;
+    assert_eq!(balance["result"]["value"], json!(2_000_000_000u64));
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_nonce_transaction_keeps_blockhash() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+
+    let payer = Keypair::new();
+    let recipient = Keypair::new();
+    let set_payer_lamports = |lamports: u64| {
+        ctx.rpc_call(
+            &fork.fork_id,
+            "setAccount",
+            json!([
+                payer.pubkey().to_string(),
+                {
+                    "lamports": lamports,
+                    "data": "",
+                    "owner": "11111111111111111111111111111111",
+                    "executable": false
+                }
+            ]),
+        )
+    };
+    set_payer_lamports(10_000_000_000).await?;
+
+    let created = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "sim_createNonceAccount",
+            json!([payer.pubkey().to_string()]),
+        )
+        .await?;
+    let nonce_account: Pubkey = created["result"]["value"]["nonceAccount"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+    let blockhash = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let nonce = stored_nonce(&ctx, &fork.fork_id, &nonce_account).await?;
+
+    // The payer can pay the fee but not the transfer; neither the nonce nor the blockhash advance
+    set_payer_lamports(100_000_000).await?;
+    let tx_response =
+        send_nonce_transfer(&ctx, &fork.fork_id, &nonce_account, &payer, &recipient).await?;
+    assert!(!tx_response["error"].is_null(), "{:?}", tx_response);
+    assert_eq!(
+        stored_nonce(&ctx, &fork.fork_id, &nonce_account).await?,
+        nonce
+    );
+    let after = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert_eq!(
+        after["result"]["value"]["blockhash"],
+        blockhash["result"]["value"]["blockhash"]
+    );
+
+    // The nonce is still usable; another recipient keeps the transaction from being a duplicate
+    set_payer_lamports(10_000_000_000).await?;
+    let next = next_nonce(&ctx, &fork.fork_id).await?;
+    let tx_response =
+        send_nonce_transfer(&ctx, &fork.fork_id, &nonce_account, &payer, &Keypair::new()).await?;
+    assert!(tx_response["error"].is_null(), "{:?}", tx_response);
+    assert_eq!(
+        stored_nonce(&ctx, &fork.fork_id, &nonce_account).await?,
+        next
+    );
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_block_production_modes() -> Result<()> {
+    let ctx = TestContext::new();
+
+    // Manual mode: transactions do not advance the slot
+    let fork = ctx
+        .create_fork_with(json!({"accounts": [], "blockProduction": {"mode": "manual"}}))
+        .await?;
+
+    let payer = Keypair::new();
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 10_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    let before = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let slot1 = before["result"]["context"]["slot"].as_u64().unwrap();
+    let blockhash: solana_sdk::hash::Hash = before["result"]["value"]["blockhash"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+
+    let instruction = transfer(&payer.pubkey(), &Keypair::new().pubkey(), 1_000_000_000);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], blockhash);
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+    ctx.rpc_call(&fork.fork_id, "sendTransaction", json!([base64_tx]))
+        .await?;
+
+    let after = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert_eq!(after["result"]["context"]["slot"].as_u64().unwrap(), slot1);
+
+    // Mining advances slot, timestamp and blockhash
+    let mined = ctx.rpc_call(&fork.fork_id, "sim_mine", json!([5])).await?;
+    assert_eq!(
+        mined["result"]["value"]["slot"].as_u64().unwrap(),
+        slot1 + 5
+    );
+    assert_ne!(
+        mined["result"]["value"]["blockhash"],
+        before["result"]["value"]["blockhash"]
+    );
+
+    // Interval mode advances on its own
+    ctx.rpc_call(
+        &fork.fork_id,
+        "sim_setBlockProduction",
+        json!([{"mode": "interval", "intervalMs": 50}]),
+    )
+    .await?;
+    tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+
+    let ticked = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert!(ticked["result"]["context"]["slot"].as_u64().unwrap() > slot1 + 5);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_import_interval_fork() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [],
+            "blockProduction": {"mode": "interval", "intervalMs": 10}
+        }))
+        .await?;
+    let payer = Keypair::new();
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 10_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    // Produce more blocks than the blockhash window holds
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let archive = ctx
+        .client
+        .get(format!("{}/forks/{}/export", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    let manifest = archive_manifest(&archive)?;
+    assert_eq!(
+        manifest["blockProduction"],
+        json!({"mode": "interval", "intervalMs": 10})
+    );
+    let recent_blockhashes = manifest["recentBlockhashes"].as_array().unwrap();
+    assert_eq!(recent_blockhashes.len(), 150);
+    assert_eq!(recent_blockhashes.last(), Some(&manifest["blockhash"]));
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks/import", ctx.base_url))
+        .body(archive)
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let imported: CreateForkResponse = response.json().await?;
+
+    // The archived blockhash is still accepted while the imported fork keeps producing blocks
+    let blockhash: solana_sdk::hash::Hash = manifest["blockhash"].as_str().unwrap().parse()?;
+    let instruction = transfer(&payer.pubkey(), &Keypair::new().pubkey(), 1_000_000_000);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], blockhash);
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+    let response = ctx
+        .rpc_call(&imported.fork_id, "sendTransaction", json!([base64_tx]))
+        .await?;
+    assert!(response["error"].is_null(), "{}", response);
+
+    let before = ctx
+        .rpc_call(&imported.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let after = ctx
+        .rpc_call(&imported.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert!(
+        after["result"]["context"]["slot"].as_u64().unwrap()
+            > before["result"]["context"]["slot"].as_u64().unwrap()
+    );
+    assert_ne!(
+        after["result"]["value"]["blockhash"],
+        before["result"]["value"]["blockhash"]
+    );
+
+    // Cleanup
+    ctx.delete_fork(&imported.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_snapshot_and_revert() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+    let account = Keypair::new().pubkey().to_string();
+
+    let set_lamports = |lamports: u64| {
+        json!([
+            account,
+            {
+                "lamports": lamports,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ])
+    };
+    let balance = |response: serde_json::Value| response["result"]["value"].as_u64().unwrap();
+
+    ctx.rpc_call(&fork.fork_id, "setAccount", set_lamports(1_000))
+        .await?;
+    let first = ctx
+        .rpc_call(&fork.fork_id, "sim_snapshot", json!([]))
+        .await?;
+    let first_id = first["result"]["value"].as_u64().unwrap();
+
+    ctx.rpc_call(&fork.fork_id, "setAccount", set_lamports(2_000))
+        .await?;
+    let second = ctx
+        .rpc_call(&fork.fork_id, "sim_snapshot", json!([]))
+        .await?;
+    let second_id = second["result"]["value"].as_u64().unwrap();
+
+    ctx.rpc_call(&fork.fork_id, "setAccount", set_lamports(3_000))
+        .await?;
+
+    // Revert to the first snapshot twice; it stays valid
+    for _ in 0..2 {
+        let reverted = ctx
+            .rpc_call(&fork.fork_id, "sim_revert", json!([first_id]))
+            .await?;
+        assert_eq!(reverted["result"]["value"], json!(true));
+        let response = ctx
+            .rpc_call(&fork.fork_id, "getBalance", json!([account]))
+            .await?;
+        assert_eq!(balance(response), 1_000);
+        ctx.rpc_call(&fork.fork_id, "setAccount", set_lamports(4_000))
+            .await?;
+    }
+
+    // Later snapshots are invalidated by the revert
+    let reverted = ctx
+        .rpc_call(&fork.fork_id, "sim_revert", json!([second_id]))
+        .await?;
+    assert!(reverted["error"].is_object());
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_clone_fork() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+    let account = Keypair::new().pubkey().to_string();
+
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            account,
+            {
+                "lamports": 1_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks/{}/clone", ctx.base_url, fork.fork_id))
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let clone: CreateForkResponse = response.json().await?;
+    assert_ne!(clone.fork_id, fork.fork_id);
+
+    // The clone starts with the source state
+    let balance = ctx
+        .rpc_call(&clone.fork_id, "getBalance", json!([account]))
+        .await?;
+    assert_eq!(balance["result"]["value"], json!(1_000_000u64));
+
+    // ...and diverges independently
+    ctx.rpc_call(
+        &clone.fork_id,
+        "setAccount",
+        json!([
+            account,
+            {
+                "lamports": 5u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([account]))
+        .await?;
+    assert_eq!(balance["result"]["value"], json!(1_000_000u64));
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+    ctx.delete_fork(&clone.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_export_import_fork() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+    let payer = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 10_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    // Roll the blockhash so the archive has to carry it over
+    ctx.rpc_call(&fork.fork_id, "sim_mine", json!([3])).await?;
+
+    let blockhash_response = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let blockhash: solana_sdk::hash::Hash = blockhash_response["result"]["value"]["blockhash"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+
+    let instruction = transfer(&payer.pubkey(), &recipient.pubkey(), 1_000_000_000);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], blockhash);
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+    ctx.rpc_call(&fork.fork_id, "sendTransaction", json!([base64_tx]))
+        .await?;
+
+    let archive = ctx
+        .client
+        .get(format!("{}/forks/{}/export", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+
+    // The source fork is not needed to import
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks/import", ctx.base_url))
+        .body(archive)
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let imported: CreateForkResponse = response.json().await?;
+
+    let balance = ctx
+        .rpc_call(
+            &imported.fork_id,
+            "getBalance",
+            json!([recipient.pubkey().to_string()]),
+        )
+        .await?;
+    assert_eq!(balance["result"]["value"], json!(1_000_000_000u64));
+
+    let imported_blockhash = ctx
+        .rpc_call(&imported.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert_eq!(
+        imported_blockhash["result"]["value"]["blockhash"],
+        blockhash_response["result"]["value"]["blockhash"]
+    );
+
+    // Garbage is rejected
+    let response = ctx
+        .client
+        .post(format!("{}/forks/import", ctx.base_url))
+        .body("not an archive")
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // Cleanup
+    ctx.delete_fork(&imported.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_diff_and_patch_fork() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+    let payer = Keypair::new();
+    let recipient = Keypair::new();
+
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 10_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    let blockhash_response = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let blockhash: solana_sdk::hash::Hash = blockhash_response["result"]["value"]["blockhash"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+
+    let instruction = transfer(&payer.pubkey(), &recipient.pubkey(), 1_000_000_000);
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], blockhash);
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+    ctx.rpc_call(&fork.fork_id, "sendTransaction", json!([base64_tx]))
+        .await?;
+
+    let diff: serde_json::Value = ctx
+        .client
+        .get(format!("{}/forks/{}/diff", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(diff["success"], true);
+    assert_eq!(diff["modified"], json!([]));
+    assert_eq!(diff["deleted"], json!([]));
+    let created = diff["created"].as_array().unwrap();
+    assert_eq!(created.len(), 2);
+    let recipient_entry = created
+        .iter()
+        .find(|entry| entry["pubkey"] == recipient.pubkey().to_string())
+        .expect("recipient should be created");
+    assert_eq!(recipient_entry["account"]["lamports"], 1_000_000_000u64);
+
+    // Applying the diff to a fresh fork reproduces the transfer
+    let target = ctx.create_fork(vec![]).await?;
+    let response = ctx
+        .client
+        .post(format!("{}/forks/{}/patch", ctx.base_url, target.fork_id))
+        .json(&diff)
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+    let patched: serde_json::Value = response.json().await?;
+    assert_eq!(patched["created"], 2);
+
+    let balance = ctx
+        .rpc_call(
+            &target.fork_id,
+            "getBalance",
+            json!([recipient.pubkey().to_string()]),
+        )
+        .await?;
+    assert_eq!(balance["result"]["value"], json!(1_000_000_000u64));
+
+    // Changes to accounts that existed at creation are reported as modifications
+    ctx.rpc_call(
+        &target.fork_id,
+        "setAccount",
+        json!([
+            recipient.pubkey().to_string(),
+            {
+                "lamports": 1_500_000_000u64,
+                "data": "AQID",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+    let archive = ctx
+        .client
+        .get(format!("{}/forks/{}/export", ctx.base_url, target.fork_id))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    let imported: CreateForkResponse = ctx
+        .client
+        .post(format!("{}/forks/import", ctx.base_url))
+        .body(archive)
+        .send()
+        .await?
+        .json()
+        .await?;
+    ctx.rpc_call(
+        &imported.fork_id,
+        "setAccount",
+        json!([
+            recipient.pubkey().to_string(),
+            {
+                "lamports": 1_000_000_000u64,
+                "data": "AQUD",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+
+    let diff: serde_json::Value = ctx
+        .client
+        .get(format!("{}/forks/{}/diff", ctx.base_url, imported.fork_id))
+        .send()
+        .await?
+        .json()
+        .await?;
+    let modified = diff["modified"].as_array().unwrap();
+    assert_eq!(modified.len(), 1);
+    assert_eq!(modified[0]["pubkey"], recipient.pubkey().to_string());
+    assert_eq!(modified[0]["lamportDelta"], -500_000_000i64);
+    assert_eq!(
+        modified[0]["dataChanges"],
+        json!([{"offset": 1, "before": "Ag==", "after": "BQ=="}])
+    );
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+    ctx.delete_fork(&target.fork_id).await?;
+    ctx.delete_fork(&imported.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reset_fork() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+    let account = Keypair::new().pubkey().to_string();
+
+    let initial = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            account,
+            {
+                "lamports": 5_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+    ctx.rpc_call(&fork.fork_id, "sim_mine", json!([5])).await?;
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks/{}/reset", ctx.base_url, fork.fork_id))
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+    let reset: serde_json::Value = response.json().await?;
+    assert_eq!(reset["forkId"], fork.fork_id);
+    assert_eq!(reset["rpcUrl"], fork.rpc_url);
+    assert_eq!(reset["slot"], initial["result"]["context"]["slot"]);
+    assert_eq!(reset["refetched"], false);
+
+    // Accounts, slot and blockhash are back to their creation values
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([account]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 0);
+    let blockhash = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert_eq!(blockhash["result"], initial["result"]);
+
+    // Refetching pulls the same (empty) account set again
+    let response = ctx
+        .client
+        .post(format!(
+            "{}/forks/{}/reset?refetch=true",
+            ctx.base_url, fork.fork_id
+        ))
+        .send()
+        .await?;
+    assert!(response.status().is_success());
+    let reset: serde_json::Value = response.json().await?;
+    assert_eq!(reset["refetched"], true);
+
+    let response = ctx
+        .client
+        .post(format!(
+            "{}/forks/00000000-0000-0000-0000-000000000000/reset",
+            ctx.base_url
+        ))
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_commitment_and_source_slot() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx
+        .create_fork_with(json!({"accounts": [], "commitment": "finalized"}))
+        .await?;
+
+    let info = ctx.get_fork(&fork.fork_id).await?;
+    assert_eq!(info["commitment"], "finalized");
+    // Without upstream accounts the source slot comes from the upstream chain tip, if reachable
+    assert!(info["sourceSlot"].is_u64() || info["sourceSlot"].is_null());
+
+    // The fork's clock starts at the recorded source slot
+    if let Some(source_slot) = info["sourceSlot"].as_u64() {
+        let blockhash = ctx
+            .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+            .await?;
+        assert_eq!(blockhash["result"]["context"]["slot"], source_slot);
+    }
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({"accounts": [], "commitment": "latest"}))
+        .send()
+        .await?;
+    assert!(response.status().is_client_error());
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lazy_fork_fetches_on_demand() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx
+        .create_fork_with(json!({"accounts": [], "lazy": true}))
+        .await?;
+
+    // Not requested at creation, fetched from upstream on first access
+    let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    let account = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "getAccountInfo",
+            json!([token_program, {"encoding": "base64"}]),
+        )
+        .await?;
+    assert!(account["error"].is_null(), "{}", account);
+    assert_eq!(account["result"]["value"]["executable"], true);
+
+    // Unknown accounts stay missing
+    let unknown = Keypair::new().pubkey().to_string();
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([unknown]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 0);
+
+    // Lazily fetched accounts are part of the creation state
+    let diff = ctx
+        .client
+        .get(format!("{}/forks/{}/diff", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(diff["created"], json!([]));
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+/// The JSON manifest of an exported fork archive
+fn archive_manifest(archive: &[u8]) -> Result<serde_json::Value> {
+    let manifest_len = u64::from_le_bytes(archive[12..20].try_into()?) as usize;
+    Ok(serde_json::from_slice(&archive[20..20 + manifest_len])?)
+}
+
+/// Replace the JSON manifest of an exported fork archive with an edited copy
+fn rewrite_manifest(archive: &[u8], edit: impl FnOnce(&mut serde_json::Value)) -> Result<Vec<u8>> {
+    let manifest_len = u64::from_le_bytes(archive[12..20].try_into()?) as usize;
+    let mut manifest = archive_manifest(archive)?;
+    edit(&mut manifest);
+    let manifest = serde_json::to_vec(&manifest)?;
+
+    let mut rewritten = archive[..12].to_vec();
+    rewritten.extend_from_slice(&(manifest.len() as u64).to_le_bytes());
+    rewritten.extend_from_slice(&manifest);
+    rewritten.extend_from_slice(&archive[20 + manifest_len..]);
+    Ok(rewritten)
+}
+
+#[tokio::test]
+async fn test_import_restores_blockhash_window() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let fork = ctx.create_fork(vec![]).await?;
+    let payer = Keypair::new();
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 10_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+    let archive = ctx
+        .client
+        .get(format!("{}/forks/{}/export", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    // Blockhashes no fresh liteSVM instance would reach are taken as they are
+    let (older, latest) = (
+        solana_sdk::hash::Hash::new_unique(),
+        solana_sdk::hash::Hash::new_unique(),
+    );
+    let tampered = rewrite_manifest(&archive, |manifest| {
+        manifest["blockhash"] = json!(latest.to_string());
+        manifest["recentBlockhashes"] = json!([older.to_string(), latest.to_string()]);
+    })?;
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks/import", ctx.base_url))
+        .body(tampered)
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let imported: CreateForkResponse = response.json().await?;
+
+    let blockhash = ctx
+        .rpc_call(&imported.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    assert_eq!(
+        blockhash["result"]["value"]["blockhash"],
+        json!(latest.to_string())
+    );
+
+    // Transactions signed against any blockhash in the window are accepted, others are not
+    let unknown = solana_sdk::hash::Hash::new_unique();
+    for (blockhash, accepted) in [(older, true), (latest, true), (unknown, false)] {
+        let instruction = transfer(&payer.pubkey(), &Pubkey::new_unique(), 1_000_000_000);
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+        transaction.sign(&[&payer], blockhash);
+        let base64_tx =
+            base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+        let response = ctx
+            .rpc_call(&imported.fork_id, "sendTransaction", json!([base64_tx]))
+            .await?;
+        assert_eq!(response["error"].is_null(), accepted, "{}", response);
+    }
+
+    // Cleanup
+    ctx.delete_fork(&imported.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_import_keeps_fork_upstream_settings() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let wallet = "Fix1P5zZKJJSocPyqHN4a86WXyH5ynXNEnt3Q27h2Hoo";
+    let excluded = Pubkey::new_unique().to_string();
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [],
+            "cluster": "fixtures",
+            "commitment": "finalized",
+            "lazy": true,
+            "idl": true,
+            "maxDepth": 2,
+            "exclude": [excluded],
+            "maxBytes": 1_000_000
+        }))
+        .await?;
+    let archive = ctx
+        .client
+        .get(format!("{}/forks/{}/export", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    let import = |archive: Vec<u8>| {
+        ctx.client
+            .post(format!("{}/forks/import", ctx.base_url))
+            .body(archive)
+            .send()
+    };
+    let response = import(archive.to_vec()).await?;
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let imported: CreateForkResponse = response.json().await?;
+
+    let info = ctx.get_fork(&imported.fork_id).await?;
+    assert_eq!(info["cluster"], "fixtures");
+    assert_eq!(info["commitment"], "finalized");
+    assert_eq!(info["idl"], true);
+    assert_eq!(
+        info["limits"],
+        json!({"maxDepth": 2, "exclude": [excluded], "maxBytes": 1_000_000})
+    );
+    assert!(info.get("upstreamUrl").is_none(), "{}", info);
+
+    // Lazy fetches go to the exported fork's cluster rather than the default one
+    let balance = ctx
+        .rpc_call(&imported.fork_id, "getBalance", json!([wallet]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 5_000_000_000u64);
+
+    // An upstream this server doesn't know or allow is rejected
+    let unknown_cluster = rewrite_manifest(&archive, |manifest| {
+        manifest["cluster"] = json!("no-such-cluster");
+    })?;
+    let response = import(unknown_cluster).await?;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "IMPORT_FAILED");
+
+    let disallowed_url = rewrite_manifest(&archive, |manifest| {
+        manifest["cluster"] = json!(null);
+        manifest["upstreamUrl"] = json!("http://169.254.169.254/");
+    })?;
+    let response = import(disallowed_url).await?;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "RPC_URL_NOT_ALLOWED");
+
+    // Cleanup
+    ctx.delete_fork(&imported.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_upstream_selection() -> Result<()> {
+    let ctx = TestContext::new();
+
+    // Forks record the cluster they were created from
+    let fork = ctx.create_fork_with(json!({"accounts": []})).await?;
+    let info = ctx.get_fork(&fork.fork_id).await?;
+    assert!(info["cluster"].is_string());
+    assert!(info["upstreamUrl"].is_null());
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({"accounts": [], "cluster": "no-such-cluster"}))
+        .send()
+        .await?;
+    assert!(!response.status().is_success());
+
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({
+            "accounts": [],
+            "cluster": info["cluster"],
+            "rpcUrl": "http://127.0.0.1:8899"
+        }))
+        .send()
+        .await?;
+    assert!(!response.status().is_success());
+
+    // rpcUrl is disabled unless the operator allows its host
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({"accounts": [], "rpcUrl": "http://169.254.169.254/"}))
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "RPC_URL_NOT_ALLOWED");
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_stats() -> Result<()> {
+    let ctx = TestContext::new();
+
+    let stats = ctx
+        .client
+        .get(format!("{}/cache/stats", ctx.base_url))
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(stats["success"], true);
+    for field in ["entries", "bytes", "hits", "misses", "evictions"] {
+        assert!(stats[field].is_u64(), "{} missing: {}", field, stats);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_from_fixtures() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let wallet = "Fix1P5zZKJJSocPyqHN4a86WXyH5ynXNEnt3Q27h2Hoo";
+    let memo_program = solana_sdk::pubkey!("MemoLLPsfXzWdZcntnXEVUgDycd9EEixiFpEcxSap6H");
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [wallet, memo_program.to_string()],
+            "cluster": "fixtures"
+        }))
+        .await?;
+    let info = ctx.get_fork(&fork.fork_id).await?;
+    assert_eq!(info["cluster"], "fixtures");
+    assert_eq!(info["sourceSlot"], 1000);
+
+    // JSON account files keep their contents, `.so` files become programs
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([wallet]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 5_000_000_000u64);
+    let program = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "getAccountInfo",
+            json!([memo_program.to_string(), {"encoding": "base64"}]),
+        )
+        .await?;
+    assert_eq!(program["result"]["value"]["executable"], true);
+
+    // The program executes
+    let payer = Keypair::new();
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([
+            payer.pubkey().to_string(),
+            {
+                "lamports": 1_000_000_000u64,
+                "data": "",
+                "owner": "11111111111111111111111111111111",
+                "executable": false
+            }
+        ]),
+    )
+    .await?;
+    let blockhash_response = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let blockhash: solana_sdk::hash::Hash = blockhash_response["result"]["value"]["blockhash"]
+        .as_str()
+        .unwrap()
+        .parse()?;
+    let memo = Instruction {
+        program_id: memo_program,
+        accounts: vec![AccountMeta::new_readonly(payer.pubkey(), true)],
+        data: b"offline".to_vec(),
+    };
+    let mut transaction = Transaction::new_with_payer(&[memo], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], blockhash);
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+    let tx_response = ctx
+        .rpc_call(&fork.fork_id, "sendTransaction", json!([base64_tx]))
+        .await?;
+    assert!(tx_response["result"].is_string(), "{}", tx_response);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_from_program_accounts_query() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    let mint = "FUNXngiL8ge5fhikywfB1KQ1ZzWowrGjqe3XbNo5jFAq";
+    let matching = "66i1Ek85e8FSthVjHiW9A8dZ936ZywT3zRYRWTdm6u1t";
+    let other_account = "EF95Veikb33zyDioydbqwaYrRoxVK7KEtA8pbRgWkoyP";
+
+    // Token accounts of one mint
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [],
+            "cluster": "fixtures",
+            "programAccounts": [{
+                "programId": token_program,
+                "filters": [{"dataSize": 165}, {"memcmp": {"offset": 0, "bytes": mint}}]
+            }]
+        }))
+        .await?;
+    assert_eq!(fork.account_count, 1);
+
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([matching]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 2_039_280u64);
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([other_account]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 0);
+
+    // More matches than maxAccounts fail the creation
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({
+            "accounts": [],
+            "cluster": "fixtures",
+            "programAccounts": [{"programId": token_program, "maxAccounts": 1}]
+        }))
+        .send()
+        .await?;
+    assert!(!response.status().is_success());
+    let body: serde_json::Value = response.json().await?;
+    assert!(
+        body["error"]["details"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("maxAccounts"),
+        "{}",
+        body
+    );
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_from_transaction() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let payer = Keypair::new_from_array([7; 32]);
+    let memo_program = solana_sdk::pubkey!("MemoLLPsfXzWdZcntnXEVUgDycd9EEixiFpEcxSap6H");
+    let wallet = solana_sdk::pubkey!("Fix1P5zZKJJSocPyqHN4a86WXyH5ynXNEnt3Q27h2Hoo");
+    let lookup_table = AddressLookupTableAccount {
+        key: solana_sdk::pubkey!("EnxLHEant3VvzQPxfixwqrU4jkyvYiVE4T3C9EMsG8oq"),
+        addresses: vec![wallet],
+    };
+
+    // The recipient is only referenced through the lookup table; the blockhash is unknown
+    // to the fork
+    let instructions = [
+        Instruction {
+            program_id: memo_program,
+            accounts: vec![AccountMeta::new_readonly(payer.pubkey(), true)],
+            data: b"replay".to_vec(),
+        },
+        transfer(&payer.pubkey(), &wallet, 1_000_000_000),
+    ];
+    let message = v0::Message::try_compile(
+        &payer.pubkey(),
+        &instructions,
+        &[lookup_table],
+        solana_sdk::hash::Hash::new_unique(),
+    )?;
+    let transaction = VersionedTransaction::try_new(VersionedMessage::V0(message), &[&payer])?;
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [],
+            "cluster": "fixtures",
+            "transaction": base64_tx,
+            "execute": true
+        }))
+        .await?;
+    // Payer, memo program, system program, lookup table and recipient
+    assert_eq!(fork.account_count, 5);
+    let executed = fork.transaction.expect("transaction executed");
+    assert!(executed.success, "{:?}", executed);
+    assert_eq!(executed.signature, transaction.signatures[0].to_string());
+
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([wallet.to_string()]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 6_000_000_000u64);
+
+    // Execute needs a transaction to execute
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({"accounts": [], "execute": true}))
+        .send()
+        .await?;
+    assert!(!response.status().is_success());
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_resolves_dependencies() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let token_account = "66i1Ek85e8FSthVjHiW9A8dZ936ZywT3zRYRWTdm6u1t";
+    let mint = "FUNXngiL8ge5fhikywfB1KQ1ZzWowrGjqe3XbNo5jFAq";
+    let lookup_table = "EnxLHEant3VvzQPxfixwqrU4jkyvYiVE4T3C9EMsG8oq";
+    let wallet = "Fix1P5zZKJJSocPyqHN4a86WXyH5ynXNEnt3Q27h2Hoo";
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [token_account, lookup_table],
+            "cluster": "fixtures"
+        }))
+        .await?;
+    assert_eq!(fork.account_count, 2);
+
+    // The token account's mint and the lookup table's addresses come along
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([mint]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 1_461_600u64);
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([wallet]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 5_000_000_000u64);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_plan_and_recursion_limits() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let token_account = "66i1Ek85e8FSthVjHiW9A8dZ936ZywT3zRYRWTdm6u1t";
+    let mint = "FUNXngiL8ge5fhikywfB1KQ1ZzWowrGjqe3XbNo5jFAq";
+    let token_program = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+    let plan = |request: serde_json::Value| {
+        ctx.client
+            .post(format!("{}/forks/plan", ctx.base_url))
+            .json(&request)
+            .send()
+    };
+
+    // The plan lists the account and its mint; the token program isn't in the fixtures
+    let response = plan(json!({"accounts": [token_account], "cluster": "fixtures"})).await?;
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await?;
+    let accounts = body["accounts"].as_array().unwrap();
+    assert_eq!(accounts.len(), 2, "{}", body);
+    let planned = |pubkey: &str| {
+        accounts
+            .iter()
+            .find(|account| account["pubkey"] == pubkey)
+            .cloned()
+            .unwrap_or_default()
+    };
+    assert_eq!(planned(token_account)["reason"], "requested");
+    assert_eq!(planned(token_account)["owner"], token_program);
+    assert_eq!(planned(token_account)["size"], 165);
+    assert_eq!(planned(mint)["reason"], "mint");
+    assert_eq!(planned(mint)["dependencyOf"], token_account);
+    assert_eq!(planned(mint)["depth"], 1);
+    assert_eq!(body["totalBytes"], 165 + 82);
+    assert_eq!(body["sourceSlot"], 1000);
+
+    // Depth and exclusions stop the recursion
+    let body: serde_json::Value = plan(json!({
+        "accounts": [token_account],
+        "cluster": "fixtures",
+        "maxDepth": 0
+    }))
+    .await?
+    .json()
+    .await?;
+    assert_eq!(body["accounts"].as_array().unwrap().len(), 1, "{}", body);
+    let body: serde_json::Value = plan(json!({
+        "accounts": [token_account],
+        "cluster": "fixtures",
+        "exclude": [mint]
+    }))
+    .await?
+    .json()
+    .await?;
+    assert_eq!(body["accounts"].as_array().unwrap().len(), 1, "{}", body);
+
+    // Exceeding the byte budget fails
+    let response = plan(json!({
+        "accounts": [token_account],
+        "cluster": "fixtures",
+        "maxBytes": 200
+    }))
+    .await?;
+    assert!(!response.status().is_success());
+    let body: serde_json::Value = response.json().await?;
+    assert!(
+        body["error"]["details"]
+            .as_str()
+            .unwrap_or_default()
+            .contains("maxBytes"),
+        "{}",
+        body
+    );
+
+    // Creation applies the same limits
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [token_account],
+            "cluster": "fixtures",
+            "maxDepth": 0
+        }))
+        .await?;
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([mint]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 0);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_reports_missing_accounts() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let token_account = "66i1Ek85e8FSthVjHiW9A8dZ936ZywT3zRYRWTdm6u1t";
+    let mint = "FUNXngiL8ge5fhikywfB1KQ1ZzWowrGjqe3XbNo5jFAq";
+    let unknown = Keypair::new().pubkey().to_string();
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [token_account, unknown],
+            "cluster": "fixtures"
+        }))
+        .await?;
+    let report = fork.report.expect("fetch report");
+    assert_eq!(report.missing, vec![unknown.clone()]);
+    assert!(report.failed.is_empty());
+    // Dependencies are loaded too; the token program isn't in the fixtures
+    assert_eq!(report.loaded.len(), 2, "{:?}", report.loaded);
+    assert!(report.loaded.contains(&mint.to_string()));
+
+    // Strict creation fails on the missing account
+    let response = ctx
+        .client
+        .post(format!("{}/forks", ctx.base_url))
+        .json(&json!({
+            "accounts": [token_account, unknown],
+            "cluster": "fixtures",
+            "strict": true
+        }))
+        .send()
+        .await?;
+    assert!(!response.status().is_success());
+    let body: serde_json::Value = response.json().await?;
+    assert!(
+        body["error"]["details"]
+            .as_str()
+            .unwrap_or_default()
+            .contains(&unknown),
+        "{}",
+        body
+    );
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_syncs_chain_context() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let payer = "GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB";
+
+    let fork = ctx
+        .create_fork_with(json!({"accounts": [payer], "cluster": "fixtures"}))
+        .await?;
+    let info = ctx.get_fork(&fork.fork_id).await?;
+    assert_eq!(info["chainSync"]["synced"], true, "{}", info);
+
+    // Clock: slot, epoch_start_timestamp, epoch, leader_schedule_epoch, unix_timestamp
+    let clock = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "getAccountInfo",
+            json!(["SysvarC1ock11111111111111111111111111111111", {"encoding": "base64"}]),
+        )
+        .await?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(clock["result"]["value"]["data"][0].as_str().unwrap())?;
+    let field =
+        |index: usize| u64::from_le_bytes(data[index * 8..index * 8 + 8].try_into().unwrap());
+    assert_eq!(field(0), 1000);
+    assert_eq!(field(2), 0);
+    assert_eq!(field(3), 1);
+    assert_eq!(field(4), 1_700_000_000);
+
+    // SlotHashes keeps the upstream entries before the fork's slot
+    let slot_hashes = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "getAccountInfo",
+            json!(["SysvarS1otHashes111111111111111111111111111", {"encoding": "base64"}]),
+        )
+        .await?;
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(slot_hashes["result"]["value"]["data"][0].as_str().unwrap())?;
+    assert_eq!(u64::from_le_bytes(data[..8].try_into()?), 2);
+    assert_eq!(u64::from_le_bytes(data[8..16].try_into()?), 999);
+
+    // The upstream blockhash is served and accepted until the fork's blockhash rolls
+    let blockhash_response = ctx
+        .rpc_call(&fork.fork_id, "getLatestBlockhash", json!([]))
+        .await?;
+    let blockhash = blockhash_response["result"]["value"]["blockhash"]
+        .as_str()
+        .unwrap();
+    assert_eq!(blockhash, solana_sdk::hash::Hash::default().to_string());
+    let payer = Keypair::new_from_array([7; 32]);
+    let recipient = Pubkey::new_unique();
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer(&payer.pubkey(), &recipient, 1_000_000_000)],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], blockhash.parse()?);
+    let base64_tx =
+        base64::engine::general_purpose::STANDARD.encode(bincode::serialize(&transaction)?);
+    let response = ctx
+        .rpc_call(&fork.fork_id, "sendTransaction", json!([base64_tx]))
+        .await?;
+    assert!(response["result"].is_string(), "{}", response);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_refresh_accounts_policies() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures`
+    let ctx = TestContext::new();
+    let payer = "GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB";
+    let mint = "FUNXngiL8ge5fhikywfB1KQ1ZzWowrGjqe3XbNo5jFAq";
+
+    let fork = ctx
+        .create_fork_with(json!({"accounts": [payer, mint], "cluster": "fixtures"}))
+        .await?;
+    ctx.rpc_call(
+        &fork.fork_id,
+        "setAccount",
+        json!([payer, {
+            "lamports": 1,
+            "data": "",
+            "owner": "11111111111111111111111111111111",
+            "executable": false
+        }]),
+    )
+    .await?;
+
+    // Untouched accounts leave out the modified payer
+    let refresh = ctx
+        .rpc_call(&fork.fork_id, "sim_refreshAccounts", json!(["untouched"]))
+        .await?;
+    let value = &refresh["result"]["value"];
+    assert_eq!(value["unchanged"], json!([mint]), "{}", refresh);
+    assert_eq!(value["changed"], json!([]));
+
+    // Modified accounts are skipped by default
+    let refresh = ctx
+        .rpc_call(&fork.fork_id, "sim_refreshAccounts", json!([[payer, mint]]))
+        .await?;
+    assert_eq!(
+        refresh["result"]["value"]["skipped"],
+        json!([payer]),
+        "{}",
+        refresh
+    );
+
+    let refresh = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "sim_refreshAccounts",
+            json!([[payer], {"policy": "failOnConflict"}]),
+        )
+        .await?;
+    assert!(
+        refresh["error"]["message"]
+            .as_str()
+            .unwrap_or_default()
+            .contains(payer),
+        "{}",
+        refresh
+    );
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([payer]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 1);
+
+    let refresh = ctx
+        .rpc_call(
+            &fork.fork_id,
+            "sim_refreshAccounts",
+            json!([[payer], {"policy": "overwrite"}]),
+        )
+        .await?;
+    assert_eq!(
+        refresh["result"]["value"]["changed"],
+        json!([payer]),
+        "{}",
+        refresh
+    );
+    let balance = ctx
+        .rpc_call(&fork.fork_id, "getBalance", json!([payer]))
+        .await?;
+    assert_eq!(balance["result"]["value"], 10_000_000_000u64);
+
+    // The refreshed state is the new creation state
+    let diff = ctx
+        .client
+        .get(format!("{}/forks/{}/diff", ctx.base_url, fork.fork_id))
+        .send()
+        .await?
+        .json::<serde_json::Value>()
+        .await?;
+    assert_eq!(diff["modified"], json!([]), "{}", diff);
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fork_ttl_extend_and_pin() -> Result<()> {
+    // Requires the service to run with `--fixtures tests/fixtures` and the default TTL limits
+    let ctx = TestContext::new();
+    let payer = "GmaDrppBC7P5ARKV8g3djiwP89vz1jLK23V2GBjuAEGB";
+
+    let fork = ctx
+        .create_fork_with(json!({
+            "accounts": [payer],
+            "cluster": "fixtures",
+            "ttlMinutes": 5,
+            "refreshOnTouch": false
+        }))
+        .await?;
+    assert_eq!(fork.ttl_minutes, 5);
+    assert_eq!(
+        fork.expires_at - fork.created_at,
+        chrono::Duration::minutes(5)
+    );
+
+    // Requests don't push the expiry back
+    ctx.rpc_call(&fork.fork_id, "getBalance", json!([payer]))
+        .await?;
+    let info = ctx.get_fork(&fork.fork_id).await?;
+    assert_eq!(info["ttlMinutes"], 5);
+    assert_eq!(info["refreshOnTouch"], false);
+    assert_eq!(info["pinned"], false);
+    assert_eq!(
+        info["expiresAt"]
+            .as_str()
+            .unwrap()
+            .parse::<chrono::DateTime<chrono::Utc>>()?,
+        fork.expires_at
+    );
+
+    let extend = |body: serde_json::Value| {
+        ctx.client
+            .post(format!("{}/forks/{}/extend", ctx.base_url, fork.fork_id))
+            .json(&body)
+            .send()
+    };
+    let response = extend(json!({"ttlMinutes": 60})).await?;
+    assert!(response.status().is_success());
+    let info: serde_json::Value = response.json().await?;
+    assert_eq!(info["ttlMinutes"], 60);
+    assert!(info["remainingMinutes"].as_i64().unwrap() >= 59, "{}", info);
+
+    // The server caps the TTL
+    let response = extend(json!({"ttlMinutes": 100_000})).await?;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "EXTEND_FAILED");
+
+    let info: serde_json::Value = extend(json!({"pinned": true})).await?.json().await?;
+    assert_eq!(info["pinned"], true);
+    assert_eq!(info["status"], "active");
+
+    // Invalid creation requests are the client's to fix
+    for request in [
+        json!({"accounts": [payer], "cluster": "fixtures", "ttlMinutes": 0}),
+        json!({"accounts": [payer], "cluster": "fixtures", "ttlMinutes": 100_000}),
+        json!({"accounts": [payer], "cluster": "no-such-cluster"}),
+        json!({"accounts": ["not-a-pubkey"], "cluster": "fixtures"}),
+    ] {
+        let response = ctx
+            .client
+            .post(format!("{}/forks", ctx.base_url))
+            .json(&request)
+            .send()
+            .await?;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{}",
+            request
+        );
+        let body: serde_json::Value = response.json().await?;
+        assert_eq!(body["error"]["code"], "FORK_CREATION_FAILED");
+    }
+
+    // Cleanup
+    ctx.delete_fork(&fork.fork_id).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reaper_evicts_expired_forks() -> Result<()> {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let data_dir = std::env::temp_dir().join(format!("sol-sim-reaper-{}", uuid::Uuid::new_v4()));
+    let data_dir_arg = data_dir.to_string_lossy().to_string();
+    let args = ["--data-dir", &data_dir_arg, "--reap-interval-secs", "1"];
+    let ctx = TestContext {
+        base_url: format!("http://localhost:{}", port),
+        client: Client::new(),
+    };
+
+    let server = TestServer::start(port, &args).await?;
+    let fork = ctx.create_fork(vec![]).await?;
+    drop(server);
+
+    // Backdate the fork's expiry while the server is down
+    let fork_dir = data_dir.join(&fork.fork_id);
+    let info_path = fork_dir.join("info.json");
+    let mut info: serde_json::Value = serde_json::from_slice(&std::fs::read(&info_path)?)?;
+    info["expires_at"] = json!(chrono::Utc::now() - chrono::Duration::minutes(1));
+    std::fs::write(&info_path, serde_json::to_vec(&info)?)?;
+    assert!(fork_dir.join("manifest.json").exists());
+
+    // The reaper drops the fork's state without any request touching it
+    let server = TestServer::start(port, &args).await?;
+    let mut evicted = false;
+    for _ in 0..50 {
+        if !fork_dir.join("manifest.json").exists() {
+            evicted = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert!(evicted, "expired fork was not evicted");
+
+    let assert_expired = |ctx: &TestContext| {
+        let rpc = ctx
+            .client
+            .post(format!("{}/rpc/{}", ctx.base_url, fork.fork_id))
+            .json(&json!({"jsonrpc": "2.0", "id": 1, "method": "getSlot"}))
+            .send();
+        let get = ctx
+            .client
+            .get(format!("{}/forks/{}", ctx.base_url, fork.fork_id))
+            .send();
+        async move {
+            let rpc = rpc.await?;
+            assert_eq!(rpc.status(), reqwest::StatusCode::GONE);
+            let body: serde_json::Value = rpc.json().await?;
+            assert!(body["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("expired"));
+
+            let get = get.await?;
+            assert_eq!(get.status(), reqwest::StatusCode::GONE);
+            let body: serde_json::Value = get.json().await?;
+            assert_eq!(body["error"]["code"], "FORK_EXPIRED");
+            anyhow::Ok(())
+        }
+    };
+    assert_expired(&ctx).await?;
+
+    // Evicted forks stay expired across restarts
+    drop(server);
+    let server = TestServer::start(port, &args).await?;
+    assert_expired(&ctx).await?;
+
+    drop(server);
+    std::fs::remove_dir_all(&data_dir)?;
+
+    Ok(())
+}